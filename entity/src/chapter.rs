@@ -16,12 +16,19 @@ pub struct Model {
     pub posted: Option<DateTimeWithTimeZone>,
     pub created_at: DateTime,
     pub updated_at: DateTime,
+    pub deleted_at: Option<DateTime>,
+    pub position: i32,
+    pub images_fetched_at: Option<DateTime>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::chapter_image::Entity")]
+    ChapterImage,
     #[sea_orm(has_many = "super::chapter_offset::Entity")]
     ChapterOffset,
+    #[sea_orm(has_many = "super::chapter_read::Entity")]
+    ChapterRead,
     #[sea_orm(
         belongs_to = "super::manga::Entity",
         from = "Column::MangaId",
@@ -30,6 +37,14 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     Manga,
+    #[sea_orm(has_many = "super::reading_history::Entity")]
+    ReadingHistory,
+}
+
+impl Related<super::chapter_image::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ChapterImage.def()
+    }
 }
 
 impl Related<super::chapter_offset::Entity> for Entity {
@@ -38,12 +53,24 @@ impl Related<super::chapter_offset::Entity> for Entity {
     }
 }
 
+impl Related<super::chapter_read::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ChapterRead.def()
+    }
+}
+
 impl Related<super::manga::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Manga.def()
     }
 }
 
+impl Related<super::reading_history::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ReadingHistory.def()
+    }
+}
+
 impl Related<super::user::Entity> for Entity {
     fn to() -> RelationDef {
         super::chapter_offset::Relation::User.def()
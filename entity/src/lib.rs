@@ -2,9 +2,24 @@
 
 pub mod prelude;
 
+pub mod account_token;
+pub mod audit_log;
 pub mod chapter;
+pub mod chapter_image;
 pub mod chapter_offset;
+pub mod chapter_read;
+pub mod collection;
+pub mod collection_manga;
+pub mod device_token_stat;
 pub mod friend;
 pub mod manga;
+pub mod notification;
+pub mod notification_setting;
 pub mod reading;
+pub mod reading_history;
+pub mod refresh_token;
+pub mod scrape_failure;
+pub mod sea_orm_active_enums;
+pub mod session;
+pub mod update_job;
 pub mod user;
@@ -25,8 +25,18 @@ pub struct Model {
 pub enum Relation {
     #[sea_orm(has_many = "super::chapter::Entity")]
     Chapter,
+    #[sea_orm(has_many = "super::collection_manga::Entity")]
+    CollectionManga,
+    #[sea_orm(has_many = "super::notification::Entity")]
+    Notification,
     #[sea_orm(has_many = "super::reading::Entity")]
     Reading,
+    #[sea_orm(has_many = "super::reading_history::Entity")]
+    ReadingHistory,
+    #[sea_orm(has_many = "super::scrape_failure::Entity")]
+    ScrapeFailure,
+    #[sea_orm(has_one = "super::update_job::Entity")]
+    UpdateJob,
 }
 
 impl Related<super::chapter::Entity> for Entity {
@@ -35,12 +45,42 @@ impl Related<super::chapter::Entity> for Entity {
     }
 }
 
+impl Related<super::collection_manga::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CollectionManga.def()
+    }
+}
+
+impl Related<super::notification::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Notification.def()
+    }
+}
+
 impl Related<super::reading::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Reading.def()
     }
 }
 
+impl Related<super::reading_history::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ReadingHistory.def()
+    }
+}
+
+impl Related<super::scrape_failure::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ScrapeFailure.def()
+    }
+}
+
+impl Related<super::update_job::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UpdateJob.def()
+    }
+}
+
 impl Related<super::user::Entity> for Entity {
     fn to() -> RelationDef {
         super::reading::Relation::User.def()
@@ -1,8 +1,22 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.19
 
+pub use super::account_token::Entity as AccountToken;
+pub use super::audit_log::Entity as AuditLog;
 pub use super::chapter::Entity as Chapter;
+pub use super::chapter_image::Entity as ChapterImage;
 pub use super::chapter_offset::Entity as ChapterOffset;
+pub use super::chapter_read::Entity as ChapterRead;
+pub use super::collection::Entity as Collection;
+pub use super::collection_manga::Entity as CollectionManga;
+pub use super::device_token_stat::Entity as DeviceTokenStat;
 pub use super::friend::Entity as Friend;
 pub use super::manga::Entity as Manga;
+pub use super::notification::Entity as Notification;
+pub use super::notification_setting::Entity as NotificationSetting;
 pub use super::reading::Entity as Reading;
+pub use super::reading_history::Entity as ReadingHistory;
+pub use super::refresh_token::Entity as RefreshToken;
+pub use super::scrape_failure::Entity as ScrapeFailure;
+pub use super::session::Entity as Session;
+pub use super::update_job::Entity as UpdateJob;
 pub use super::user::Entity as User;
@@ -1,5 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.19
 
+use super::sea_orm_active_enums::ReadingStatus;
 use sea_orm::entity::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
@@ -12,6 +13,9 @@ pub struct Model {
     pub progress: i32,
     pub created_at: DateTime,
     pub updated_at: DateTime,
+    pub status: ReadingStatus,
+    pub muted: bool,
+    pub notify_caught_up: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
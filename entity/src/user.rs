@@ -17,14 +17,49 @@ pub struct Model {
     pub created_at: DateTime,
     pub updated_at: DateTime,
     pub device_ids: Vec<String>,
+    pub email_verified: bool,
+    pub banned_at: Option<DateTime>,
+    pub ban_reason: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::account_token::Entity")]
+    AccountToken,
+    #[sea_orm(has_many = "super::audit_log::Entity")]
+    AuditLog,
     #[sea_orm(has_many = "super::chapter_offset::Entity")]
     ChapterOffset,
+    #[sea_orm(has_many = "super::chapter_read::Entity")]
+    ChapterRead,
+    #[sea_orm(has_many = "super::collection::Entity")]
+    Collection,
+    #[sea_orm(has_many = "super::device_token_stat::Entity")]
+    DeviceTokenStat,
+    #[sea_orm(has_many = "super::notification::Entity")]
+    Notification,
+    #[sea_orm(has_one = "super::notification_setting::Entity")]
+    NotificationSetting,
     #[sea_orm(has_many = "super::reading::Entity")]
     Reading,
+    #[sea_orm(has_many = "super::reading_history::Entity")]
+    ReadingHistory,
+    #[sea_orm(has_many = "super::refresh_token::Entity")]
+    RefreshToken,
+    #[sea_orm(has_many = "super::session::Entity")]
+    Session,
+}
+
+impl Related<super::account_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AccountToken.def()
+    }
+}
+
+impl Related<super::audit_log::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AuditLog.def()
+    }
 }
 
 impl Related<super::chapter_offset::Entity> for Entity {
@@ -33,12 +68,60 @@ impl Related<super::chapter_offset::Entity> for Entity {
     }
 }
 
+impl Related<super::chapter_read::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ChapterRead.def()
+    }
+}
+
+impl Related<super::collection::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Collection.def()
+    }
+}
+
+impl Related<super::device_token_stat::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::DeviceTokenStat.def()
+    }
+}
+
+impl Related<super::notification::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Notification.def()
+    }
+}
+
+impl Related<super::notification_setting::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::NotificationSetting.def()
+    }
+}
+
 impl Related<super::reading::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Reading.def()
     }
 }
 
+impl Related<super::reading_history::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ReadingHistory.def()
+    }
+}
+
+impl Related<super::refresh_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RefreshToken.def()
+    }
+}
+
+impl Related<super::session::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Session.def()
+    }
+}
+
 impl Related<super::chapter::Entity> for Entity {
     fn to() -> RelationDef {
         super::chapter_offset::Relation::Chapter.def()
@@ -1,3 +1,3 @@
 #!/bin/sh
 export RUSTUP_TOOLCHAIN=nightly
-sea-orm-cli generate entity -o ./entity/src --lib
\ No newline at end of file
+sea-orm-cli generate entity -o ./entity/src --lib --with-copy-enums
\ No newline at end of file
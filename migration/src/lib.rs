@@ -13,6 +13,31 @@ mod m20230212_132547_add_page_column_to_chapter_offset;
 mod m20230219_170615_add_device_ids_column_to_user;
 mod m20231116_195236_fix_timestamps;
 mod m20231125_223257_add_status_to_manga;
+mod m20261018_120000_create_refresh_token;
+mod m20261018_120100_create_session;
+mod m20261018_120200_add_session_id_to_refresh_token;
+mod m20261018_120300_add_email_verified_to_user;
+mod m20261018_120400_create_account_token;
+mod m20261018_120500_add_ban_columns_to_user;
+mod m20261018_120600_create_audit_log;
+mod m20261018_120700_add_status_to_reading;
+mod m20261018_120800_create_collection;
+mod m20261018_120900_create_collection_manga;
+mod m20261018_121000_create_reading_history;
+mod m20261018_121100_create_chapter_read;
+mod m20261018_121200_add_deleted_at_to_chapter;
+mod m20261018_121300_create_scrape_failure;
+mod m20261018_121400_create_update_job;
+mod m20261018_121500_create_chapter_image;
+mod m20261018_121600_create_notification_setting;
+mod m20261018_121700_create_notification;
+mod m20261018_121800_add_notify_columns_to_reading;
+mod m20261018_121900_add_quiet_hours_to_notification_setting;
+mod m20261018_122000_add_pending_to_notification;
+mod m20261018_122100_create_device_token_stat;
+mod m20261018_122200_use_enum_types;
+mod m20261018_122300_add_position_to_chapter;
+mod m20261018_122400_add_images_fetched_at_to_chapter;
 
 pub struct Migrator;
 
@@ -31,6 +56,31 @@ impl MigratorTrait for Migrator {
             Box::new(m20230219_170615_add_device_ids_column_to_user::Migration),
             Box::new(m20231116_195236_fix_timestamps::Migration),
             Box::new(m20231125_223257_add_status_to_manga::Migration),
+            Box::new(m20261018_120000_create_refresh_token::Migration),
+            Box::new(m20261018_120100_create_session::Migration),
+            Box::new(m20261018_120200_add_session_id_to_refresh_token::Migration),
+            Box::new(m20261018_120300_add_email_verified_to_user::Migration),
+            Box::new(m20261018_120400_create_account_token::Migration),
+            Box::new(m20261018_120500_add_ban_columns_to_user::Migration),
+            Box::new(m20261018_120600_create_audit_log::Migration),
+            Box::new(m20261018_120700_add_status_to_reading::Migration),
+            Box::new(m20261018_120800_create_collection::Migration),
+            Box::new(m20261018_120900_create_collection_manga::Migration),
+            Box::new(m20261018_121000_create_reading_history::Migration),
+            Box::new(m20261018_121100_create_chapter_read::Migration),
+            Box::new(m20261018_121200_add_deleted_at_to_chapter::Migration),
+            Box::new(m20261018_121300_create_scrape_failure::Migration),
+            Box::new(m20261018_121400_create_update_job::Migration),
+            Box::new(m20261018_121500_create_chapter_image::Migration),
+            Box::new(m20261018_121600_create_notification_setting::Migration),
+            Box::new(m20261018_121700_create_notification::Migration),
+            Box::new(m20261018_121800_add_notify_columns_to_reading::Migration),
+            Box::new(m20261018_121900_add_quiet_hours_to_notification_setting::Migration),
+            Box::new(m20261018_122000_add_pending_to_notification::Migration),
+            Box::new(m20261018_122100_create_device_token_stat::Migration),
+            Box::new(m20261018_122200_use_enum_types::Migration),
+            Box::new(m20261018_122300_add_position_to_chapter::Migration),
+            Box::new(m20261018_122400_add_images_fetched_at_to_chapter::Migration),
         ]
     }
 }
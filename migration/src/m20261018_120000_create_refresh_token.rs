@@ -0,0 +1,68 @@
+use sea_orm_migration::prelude::*;
+
+use crate::extension::timestamps::TimestampExt;
+use crate::m20221127_174334_create_user::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RefreshToken::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RefreshToken::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(RefreshToken::UserId).integer().not_null())
+                    .col(
+                        ColumnDef::new(RefreshToken::TokenHash)
+                            .string_len(64)
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(RefreshToken::ExpiresAt).timestamp().not_null())
+                    .col(
+                        ColumnDef::new(RefreshToken::Revoked)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(RefreshToken::Table, RefreshToken::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .take(),
+            )
+            .await?;
+
+        manager.timestamps(RefreshToken::Table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RefreshToken::Table).take())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+pub(crate) enum RefreshToken {
+    Table,
+    Id,
+    UserId,
+    TokenHash,
+    ExpiresAt,
+    Revoked,
+}
@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+
+use crate::extension::timestamps::TimestampExt;
+use crate::m20221127_174334_create_user::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Session::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Session::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Session::UserId).integer().not_null())
+                    .col(ColumnDef::new(Session::UserAgent).string_len(511))
+                    .col(ColumnDef::new(Session::RemoteAddr).string_len(63))
+                    .col(
+                        ColumnDef::new(Session::LastSeenAt)
+                            .timestamp()
+                            .extra("DEFAULT NOW()".to_owned())
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Session::Table, Session::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .take(),
+            )
+            .await?;
+
+        manager.timestamps(Session::Table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(Session::Table).take()).await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+pub(crate) enum Session {
+    Table,
+    Id,
+    UserId,
+    UserAgent,
+    RemoteAddr,
+    LastSeenAt,
+}
@@ -0,0 +1,57 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20261018_120000_create_refresh_token::RefreshToken;
+use crate::m20261018_120100_create_session::Session;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum RefreshTokenWithSessionId {
+    SessionId,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Existing refresh tokens do not belong to a session, so they are dropped
+        manager
+            .exec_stmt(Query::delete().from_table(RefreshToken::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RefreshToken::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(RefreshTokenWithSessionId::SessionId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk-refresh_token-session_id")
+                            .from_tbl(RefreshToken::Table)
+                            .from_col(RefreshTokenWithSessionId::SessionId)
+                            .to_tbl(Session::Table)
+                            .to_col(Session::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .take(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RefreshToken::Table)
+                    .drop_foreign_key(Alias::new("fk-refresh_token-session_id"))
+                    .drop_column(RefreshTokenWithSessionId::SessionId)
+                    .take(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,63 @@
+use sea_orm_migration::prelude::*;
+
+use crate::extension::timestamps::TimestampExt;
+use crate::m20221127_174334_create_user::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AccountToken::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AccountToken::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(AccountToken::UserId).integer().not_null())
+                    .col(ColumnDef::new(AccountToken::Purpose).string_len(31).not_null())
+                    .col(
+                        ColumnDef::new(AccountToken::TokenHash)
+                            .string_len(64)
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(AccountToken::ExpiresAt).timestamp().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(AccountToken::Table, AccountToken::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .take(),
+            )
+            .await?;
+
+        manager.timestamps(AccountToken::Table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AccountToken::Table).take())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+pub(crate) enum AccountToken {
+    Table,
+    Id,
+    UserId,
+    Purpose,
+    TokenHash,
+    ExpiresAt,
+}
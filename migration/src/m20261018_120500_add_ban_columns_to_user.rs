@@ -0,0 +1,39 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221127_174334_create_user::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum UserWithBan {
+    BannedAt,
+    BanReason,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column_if_not_exists(ColumnDef::new(UserWithBan::BannedAt).timestamp())
+                    .add_column_if_not_exists(ColumnDef::new(UserWithBan::BanReason).string_len(511))
+                    .take(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(UserWithBan::BannedAt)
+                    .drop_column(UserWithBan::BanReason)
+                    .take(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,56 @@
+use sea_orm_migration::prelude::*;
+
+use crate::extension::timestamps::TimestampExt;
+use crate::m20221127_174334_create_user::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuditLog::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AuditLog::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(AuditLog::ActorId).integer())
+                    .col(ColumnDef::new(AuditLog::Action).string_len(63).not_null())
+                    .col(ColumnDef::new(AuditLog::TargetId).integer().not_null())
+                    .col(ColumnDef::new(AuditLog::Details).json_binary().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(AuditLog::Table, AuditLog::ActorId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .take(),
+            )
+            .await?;
+
+        manager.timestamps(AuditLog::Table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(AuditLog::Table).take()).await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+pub(crate) enum AuditLog {
+    Table,
+    Id,
+    ActorId,
+    Action,
+    TargetId,
+    Details,
+}
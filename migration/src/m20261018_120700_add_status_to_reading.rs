@@ -0,0 +1,41 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221130_215753_create_reading::Reading;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum ReadingWithStatus {
+    Status,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reading::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(ReadingWithStatus::Status)
+                            .string_len(15)
+                            .not_null()
+                            .default("reading"),
+                    )
+                    .take(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reading::Table)
+                    .drop_column(ReadingWithStatus::Status)
+                    .take(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,59 @@
+use sea_orm_migration::prelude::*;
+
+use crate::extension::timestamps::TimestampExt;
+use crate::m20221127_174334_create_user::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Collection::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Collection::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Collection::UserId).integer().not_null())
+                    .col(ColumnDef::new(Collection::Name).string_len(63).not_null())
+                    .index(
+                        Index::create()
+                            .name("collection_user_id_name_key")
+                            .unique()
+                            .col(Collection::UserId)
+                            .col(Collection::Name),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Collection::Table, Collection::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .take(),
+            )
+            .await?;
+
+        manager.timestamps(Collection::Table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(Collection::Table).take()).await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+pub(crate) enum Collection {
+    Table,
+    Id,
+    UserId,
+    Name,
+}
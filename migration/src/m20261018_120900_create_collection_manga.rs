@@ -0,0 +1,66 @@
+use sea_orm_migration::prelude::*;
+
+use crate::extension::timestamps::TimestampExt;
+use crate::m20221130_215742_create_manga::Manga;
+use crate::m20261018_120800_create_collection::Collection;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(CollectionManga::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(CollectionManga::CollectionId).integer().not_null())
+                    .col(ColumnDef::new(CollectionManga::MangaId).integer().not_null())
+                    .primary_key(
+                        Index::create()
+                            .col(CollectionManga::CollectionId)
+                            .col(CollectionManga::MangaId),
+                    )
+                    .col(
+                        ColumnDef::new(CollectionManga::Position)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(CollectionManga::Table, CollectionManga::CollectionId)
+                            .to(Collection::Table, Collection::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(CollectionManga::Table, CollectionManga::MangaId)
+                            .to(Manga::Table, Manga::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .take(),
+            )
+            .await?;
+
+        manager.timestamps(CollectionManga::Table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CollectionManga::Table).take())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum CollectionManga {
+    Table,
+    CollectionId,
+    MangaId,
+    Position,
+}
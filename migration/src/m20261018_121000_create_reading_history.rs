@@ -0,0 +1,87 @@
+use sea_orm_migration::prelude::*;
+
+use crate::extension::timestamps::{Timestamp, TimestampExt};
+use crate::m20221127_174334_create_user::User;
+use crate::m20221130_215742_create_manga::Manga;
+use crate::m20221130_215749_create_chapter::Chapter;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ReadingHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ReadingHistory::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ReadingHistory::UserId).integer().not_null())
+                    .col(ColumnDef::new(ReadingHistory::MangaId).integer().not_null())
+                    .col(ColumnDef::new(ReadingHistory::ChapterId).integer())
+                    .col(ColumnDef::new(ReadingHistory::Progress).integer().not_null())
+                    .col(ColumnDef::new(ReadingHistory::Read).boolean().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ReadingHistory::Table, ReadingHistory::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ReadingHistory::Table, ReadingHistory::MangaId)
+                            .to(Manga::Table, Manga::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ReadingHistory::Table, ReadingHistory::ChapterId)
+                            .to(Chapter::Table, Chapter::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .take(),
+            )
+            .await?;
+
+        manager.timestamps(ReadingHistory::Table).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("reading_history_user_id_created_at_idx")
+                    .table(ReadingHistory::Table)
+                    .col(ReadingHistory::UserId)
+                    .col(Timestamp::CreatedAt)
+                    .take(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ReadingHistory::Table).take())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum ReadingHistory {
+    Table,
+    Id,
+    UserId,
+    MangaId,
+    ChapterId,
+    Progress,
+    Read,
+}
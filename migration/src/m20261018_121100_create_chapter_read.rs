@@ -0,0 +1,74 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, Statement};
+
+use crate::extension::timestamps::TimestampExt;
+use crate::m20221127_174334_create_user::User;
+use crate::m20221130_215749_create_chapter::Chapter;
+
+/// Mark the first chapters of every reading as read, up to its progress
+const BACKFILL_QUERY: &str = r#"
+INSERT INTO chapter_read (user_id, chapter_id)
+SELECT reading.user_id, numbered.id
+FROM reading
+JOIN (
+    SELECT id, manga_id, ROW_NUMBER() OVER (PARTITION BY manga_id ORDER BY id) AS index FROM chapter
+) numbered ON numbered.manga_id = reading.manga_id AND numbered.index <= reading.progress
+ON CONFLICT DO NOTHING"#;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ChapterRead::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(ChapterRead::UserId).integer().not_null())
+                    .col(ColumnDef::new(ChapterRead::ChapterId).integer().not_null())
+                    .primary_key(Index::create().col(ChapterRead::UserId).col(ChapterRead::ChapterId))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ChapterRead::Table, ChapterRead::ChapterId)
+                            .to(Chapter::Table, Chapter::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ChapterRead::Table, ChapterRead::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .take(),
+            )
+            .await?;
+
+        manager.timestamps(ChapterRead::Table).await?;
+
+        manager
+            .get_connection()
+            .execute(Statement::from_string(
+                manager.get_database_backend(),
+                String::from(BACKFILL_QUERY),
+            ))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(ChapterRead::Table).take()).await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum ChapterRead {
+    Table,
+    UserId,
+    ChapterId,
+}
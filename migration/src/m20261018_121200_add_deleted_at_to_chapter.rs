@@ -0,0 +1,36 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221130_215749_create_chapter::Chapter;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum ChapterWithDeletedAt {
+    DeletedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Chapter::Table)
+                    .add_column_if_not_exists(ColumnDef::new(ChapterWithDeletedAt::DeletedAt).timestamp())
+                    .take(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Chapter::Table)
+                    .drop_column(ChapterWithDeletedAt::DeletedAt)
+                    .take(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,67 @@
+use sea_orm_migration::prelude::*;
+
+use crate::extension::timestamps::{Timestamp, TimestampExt};
+use crate::m20221130_215742_create_manga::Manga;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ScrapeFailure::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ScrapeFailure::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ScrapeFailure::MangaId).integer().not_null())
+                    .col(ColumnDef::new(ScrapeFailure::ErrorType).string_len(63).not_null())
+                    .col(ColumnDef::new(ScrapeFailure::Message).string().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ScrapeFailure::Table, ScrapeFailure::MangaId)
+                            .to(Manga::Table, Manga::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .take(),
+            )
+            .await?;
+
+        manager.timestamps(ScrapeFailure::Table).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("scrape_failure_manga_id_created_at_idx")
+                    .table(ScrapeFailure::Table)
+                    .col(ScrapeFailure::MangaId)
+                    .col(Timestamp::CreatedAt)
+                    .take(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ScrapeFailure::Table).take())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum ScrapeFailure {
+    Table,
+    Id,
+    MangaId,
+    ErrorType,
+    Message,
+}
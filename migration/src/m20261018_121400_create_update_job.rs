@@ -0,0 +1,77 @@
+use sea_orm_migration::prelude::*;
+
+use crate::extension::timestamps::TimestampExt;
+use crate::m20221130_215742_create_manga::Manga;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UpdateJob::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UpdateJob::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(UpdateJob::MangaId).integer().not_null().unique_key())
+                    .col(ColumnDef::new(UpdateJob::Host).string_len(255).not_null())
+                    .col(
+                        ColumnDef::new(UpdateJob::RunAt)
+                            .timestamp()
+                            .extra("DEFAULT NOW()".to_owned())
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(UpdateJob::Attempts).integer().not_null().default(0))
+                    .col(ColumnDef::new(UpdateJob::LockedAt).timestamp())
+                    .col(ColumnDef::new(UpdateJob::LockedBy).string_len(63))
+                    .col(ColumnDef::new(UpdateJob::LastError).string())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(UpdateJob::Table, UpdateJob::MangaId)
+                            .to(Manga::Table, Manga::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .take(),
+            )
+            .await?;
+
+        manager.timestamps(UpdateJob::Table).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("update_job_run_at_idx")
+                    .table(UpdateJob::Table)
+                    .col(UpdateJob::RunAt)
+                    .take(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(UpdateJob::Table).take()).await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum UpdateJob {
+    Table,
+    Id,
+    MangaId,
+    Host,
+    RunAt,
+    Attempts,
+    LockedAt,
+    LockedBy,
+    LastError,
+}
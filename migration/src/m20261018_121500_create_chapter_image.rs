@@ -0,0 +1,56 @@
+use sea_orm_migration::prelude::*;
+
+use crate::extension::timestamps::TimestampExt;
+use crate::m20221130_215749_create_chapter::Chapter;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ChapterImage::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(ChapterImage::ChapterId).integer().not_null())
+                    .col(ColumnDef::new(ChapterImage::Position).integer().not_null())
+                    .primary_key(Index::create().col(ChapterImage::ChapterId).col(ChapterImage::Position))
+                    .col(ColumnDef::new(ChapterImage::Url).text().not_null())
+                    .col(
+                        ColumnDef::new(ChapterImage::FetchedAt)
+                            .timestamp()
+                            .extra("DEFAULT NOW()".to_owned())
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ChapterImage::Table, ChapterImage::ChapterId)
+                            .to(Chapter::Table, Chapter::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .take(),
+            )
+            .await?;
+
+        manager.timestamps(ChapterImage::Table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ChapterImage::Table).take())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum ChapterImage {
+    Table,
+    ChapterId,
+    Position,
+    Url,
+    FetchedAt,
+}
@@ -0,0 +1,76 @@
+use sea_orm_migration::prelude::*;
+
+use crate::extension::timestamps::TimestampExt;
+use crate::m20221127_174334_create_user::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(NotificationSetting::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(NotificationSetting::UserId)
+                            .integer()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(NotificationSetting::Fcm)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(
+                        ColumnDef::new(NotificationSetting::Email)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(ColumnDef::new(NotificationSetting::WebhookUrl).string())
+                    .col(
+                        ColumnDef::new(NotificationSetting::PushService)
+                            .string_len(15)
+                            .not_null()
+                            .default("ntfy"),
+                    )
+                    .col(ColumnDef::new(NotificationSetting::PushUrl).string())
+                    .col(ColumnDef::new(NotificationSetting::PushToken).string())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(NotificationSetting::Table, NotificationSetting::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .take(),
+            )
+            .await?;
+
+        manager.timestamps(NotificationSetting::Table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(NotificationSetting::Table).take())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum NotificationSetting {
+    Table,
+    UserId,
+    Fcm,
+    Email,
+    WebhookUrl,
+    PushService,
+    PushUrl,
+    PushToken,
+}
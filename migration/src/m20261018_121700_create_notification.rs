@@ -0,0 +1,81 @@
+use sea_orm_migration::prelude::*;
+
+use crate::extension::timestamps::TimestampExt;
+use crate::m20221127_174334_create_user::User;
+use crate::m20221130_215742_create_manga::Manga;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Notification::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Notification::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Notification::UserId).integer().not_null())
+                    .col(ColumnDef::new(Notification::MangaId).integer().not_null())
+                    .col(
+                        ColumnDef::new(Notification::ChapterIds)
+                            .array(ColumnType::Integer)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Notification::Read).boolean().not_null().default(false))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Notification::Table, Notification::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Notification::Table, Notification::MangaId)
+                            .to(Manga::Table, Manga::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .take(),
+            )
+            .await?;
+
+        manager.timestamps(Notification::Table).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("notification_user_id_read_idx")
+                    .table(Notification::Table)
+                    .col(Notification::UserId)
+                    .col(Notification::Read)
+                    .take(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Notification::Table).take())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Notification {
+    Table,
+    Id,
+    UserId,
+    MangaId,
+    ChapterIds,
+    Read,
+}
@@ -0,0 +1,49 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221130_215753_create_reading::Reading;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum ReadingWithNotify {
+    Muted,
+    NotifyCaughtUp,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reading::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(ReadingWithNotify::Muted)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .add_column_if_not_exists(
+                        ColumnDef::new(ReadingWithNotify::NotifyCaughtUp)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .take(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reading::Table)
+                    .drop_column(ReadingWithNotify::Muted)
+                    .drop_column(ReadingWithNotify::NotifyCaughtUp)
+                    .take(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,46 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum NotificationSetting {
+    Table,
+    QuietStart,
+    QuietEnd,
+    Timezone,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NotificationSetting::Table)
+                    .add_column_if_not_exists(ColumnDef::new(NotificationSetting::QuietStart).time())
+                    .add_column_if_not_exists(ColumnDef::new(NotificationSetting::QuietEnd).time())
+                    .add_column_if_not_exists(
+                        ColumnDef::new(NotificationSetting::Timezone)
+                            .string_len(63)
+                            .not_null()
+                            .default("UTC"),
+                    )
+                    .take(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NotificationSetting::Table)
+                    .drop_column(NotificationSetting::QuietStart)
+                    .drop_column(NotificationSetting::QuietEnd)
+                    .drop_column(NotificationSetting::Timezone)
+                    .take(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,57 @@
+use sea_orm_migration::prelude::*;
+
+use crate::extension::timestamps::TimestampExt;
+use crate::m20221127_174334_create_user::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(DeviceTokenStat::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(DeviceTokenStat::UserId).integer().not_null())
+                    .col(ColumnDef::new(DeviceTokenStat::Token).text().not_null())
+                    .primary_key(Index::create().col(DeviceTokenStat::UserId).col(DeviceTokenStat::Token))
+                    .col(ColumnDef::new(DeviceTokenStat::Sent).integer().not_null().default(0))
+                    .col(ColumnDef::new(DeviceTokenStat::Failed).integer().not_null().default(0))
+                    .col(ColumnDef::new(DeviceTokenStat::LastSentAt).timestamp())
+                    .col(ColumnDef::new(DeviceTokenStat::LastFailedAt).timestamp())
+                    .col(ColumnDef::new(DeviceTokenStat::LastError).string())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(DeviceTokenStat::Table, DeviceTokenStat::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .take(),
+            )
+            .await?;
+
+        manager.timestamps(DeviceTokenStat::Table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DeviceTokenStat::Table).take())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum DeviceTokenStat {
+    Table,
+    UserId,
+    Token,
+    Sent,
+    Failed,
+    LastSentAt,
+    LastFailedAt,
+    LastError,
+}
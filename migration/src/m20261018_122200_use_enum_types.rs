@@ -0,0 +1,95 @@
+use sea_orm_migration::prelude::extension::postgres::Type;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum ReadingStatus {
+    #[iden = "reading_status"]
+    Enum,
+    Completed,
+    Dropped,
+    #[iden = "on-hold"]
+    OnHold,
+    #[iden = "plan-to-read"]
+    PlanToRead,
+    Reading,
+}
+
+#[derive(Iden)]
+enum PushService {
+    #[iden = "push_service"]
+    Enum,
+    Gotify,
+    Ntfy,
+}
+
+/// Change a string column to an enum type, keeping its values and default
+const TO_ENUM: [&str; 2] = [
+    r#"ALTER TABLE "reading"
+    ALTER COLUMN "status" DROP DEFAULT,
+    ALTER COLUMN "status" TYPE "reading_status" USING "status"::"reading_status",
+    ALTER COLUMN "status" SET DEFAULT 'reading'"#,
+    r#"ALTER TABLE "notification_setting"
+    ALTER COLUMN "push_service" DROP DEFAULT,
+    ALTER COLUMN "push_service" TYPE "push_service" USING "push_service"::"push_service",
+    ALTER COLUMN "push_service" SET DEFAULT 'ntfy'"#,
+];
+
+const TO_STRING: [&str; 2] = [
+    r#"ALTER TABLE "reading"
+    ALTER COLUMN "status" DROP DEFAULT,
+    ALTER COLUMN "status" TYPE varchar(15) USING "status"::text,
+    ALTER COLUMN "status" SET DEFAULT 'reading'"#,
+    r#"ALTER TABLE "notification_setting"
+    ALTER COLUMN "push_service" DROP DEFAULT,
+    ALTER COLUMN "push_service" TYPE varchar(15) USING "push_service"::text,
+    ALTER COLUMN "push_service" SET DEFAULT 'ntfy'"#,
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_type(
+                Type::create()
+                    .as_enum(ReadingStatus::Enum)
+                    .values([
+                        ReadingStatus::Completed,
+                        ReadingStatus::Dropped,
+                        ReadingStatus::OnHold,
+                        ReadingStatus::PlanToRead,
+                        ReadingStatus::Reading,
+                    ])
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_type(
+                Type::create()
+                    .as_enum(PushService::Enum)
+                    .values([PushService::Gotify, PushService::Ntfy])
+                    .to_owned(),
+            )
+            .await?;
+
+        for sql in TO_ENUM {
+            manager.get_connection().execute_unprepared(sql).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for sql in TO_STRING {
+            manager.get_connection().execute_unprepared(sql).await?;
+        }
+
+        manager
+            .drop_type(Type::drop().name(ReadingStatus::Enum).to_owned())
+            .await?;
+        manager.drop_type(Type::drop().name(PushService::Enum).to_owned()).await
+    }
+}
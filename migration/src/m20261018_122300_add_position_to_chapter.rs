@@ -0,0 +1,70 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221130_215749_create_chapter::Chapter;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum ChapterWithPosition {
+    Position,
+}
+
+/// Saved chapters were in reading order by ID, which is the best guess until they are scraped again
+const FILL_POSITION_QUERY: &str = r#"
+UPDATE chapter SET position = ordered.position FROM (
+    SELECT id, (ROW_NUMBER() OVER (PARTITION BY manga_id ORDER BY id) - 1)::int AS position FROM chapter
+) ordered
+WHERE chapter.id = ordered.id"#;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Chapter::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(ChapterWithPosition::Position)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .take(),
+            )
+            .await?;
+
+        manager.get_connection().execute_unprepared(FILL_POSITION_QUERY).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("chapter_manga_id_position_idx")
+                    .table(Chapter::Table)
+                    .col(Chapter::MangaId)
+                    .col(ChapterWithPosition::Position)
+                    .take(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("chapter_manga_id_position_idx")
+                    .table(Chapter::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Chapter::Table)
+                    .drop_column(ChapterWithPosition::Position)
+                    .take(),
+            )
+            .await
+    }
+}
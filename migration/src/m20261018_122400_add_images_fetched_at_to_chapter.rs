@@ -0,0 +1,50 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221130_215749_create_chapter::Chapter;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum ChapterWithImagesFetchedAt {
+    ImagesFetchedAt,
+}
+
+/// Chapters of which the images were saved before are already scraped
+const FILL_IMAGES_FETCHED_AT_QUERY: &str = r#"
+UPDATE chapter SET images_fetched_at = fetched.fetched_at FROM (
+    SELECT chapter_id, MIN(fetched_at) AS fetched_at FROM chapter_image GROUP BY chapter_id
+) fetched
+WHERE chapter.id = fetched.chapter_id"#;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Chapter::Table)
+                    .add_column_if_not_exists(ColumnDef::new(ChapterWithImagesFetchedAt::ImagesFetchedAt).timestamp())
+                    .take(),
+            )
+            .await?;
+
+        manager
+            .get_connection()
+            .execute_unprepared(FILL_IMAGES_FETCHED_AT_QUERY)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Chapter::Table)
+                    .drop_column(ChapterWithImagesFetchedAt::ImagesFetchedAt)
+                    .take(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,25 @@
+use sea_orm::prelude::DateTime;
+use sea_orm::FromQueryResult;
+
+use crate::proto::CollectionReply;
+
+#[derive(Debug, FromQueryResult)]
+pub struct Full {
+    pub id: i32,
+    pub name: String,
+    pub count_manga: i64,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+impl From<Full> for CollectionReply {
+    fn from(value: Full) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            count_manga: value.count_manga,
+            created_at: value.created_at.and_utc().timestamp_millis(),
+            updated_at: value.updated_at.and_utc().timestamp_millis(),
+        }
+    }
+}
@@ -1,7 +1,8 @@
+use entity::sea_orm_active_enums::ReadingStatus;
 use sea_orm::prelude::{DateTime, DateTimeWithTimeZone};
 use sea_orm::{DeriveColumn, EnumIter, FromQueryResult};
 
-use crate::proto::MangaReply;
+use crate::proto::{self, MangaReply};
 
 #[derive(Debug, Copy, Clone, EnumIter, DeriveColumn)]
 pub enum Minimal {
@@ -19,6 +20,9 @@ pub struct Full {
     pub status: String,
     pub is_ongoing: bool,
     pub progress: Option<i32>,
+    pub reading_status: Option<ReadingStatus>,
+    pub muted: Option<bool>,
+    pub notify_caught_up: Option<bool>,
     pub genres: Vec<String>,
     pub authors: Vec<String>,
     pub alt_titles: Vec<String>,
@@ -45,6 +49,11 @@ impl From<Full> for MangaReply {
             alt_titles: value.alt_titles,
             count_chapters: value.count_chapters,
             reading_progress: value.progress,
+            reading_status: value
+                .reading_status
+                .map(|status| proto::ReadingStatus::from(status).into()),
+            reading_muted: value.muted,
+            reading_notify_caught_up: value.notify_caught_up,
             last: value.last.map(|date| date.timestamp_millis()),
             next: value.next.map(|date| date.timestamp_millis()),
             created_at: value.created_at.and_utc().timestamp_millis(),
@@ -1,3 +1,8 @@
 pub mod chapter;
+pub mod collection;
 pub mod manga;
+pub mod notification;
+pub mod notification_setting;
+pub mod reading;
+pub mod reading_history;
 pub mod user;
@@ -0,0 +1,29 @@
+use sea_orm::prelude::DateTime;
+use sea_orm::FromQueryResult;
+
+use crate::proto::NotificationReply;
+
+#[derive(Debug, FromQueryResult)]
+pub struct Full {
+    pub id: i32,
+    pub manga_id: i32,
+    pub chapter_ids: Vec<i32>,
+    pub read: bool,
+    pub created_at: DateTime,
+
+    // special
+    pub manga_title: String,
+}
+
+impl From<Full> for NotificationReply {
+    fn from(value: Full) -> Self {
+        Self {
+            id: value.id,
+            manga_id: value.manga_id,
+            manga_title: value.manga_title,
+            chapter_ids: value.chapter_ids,
+            read: value.read,
+            created_at: value.created_at.and_utc().timestamp_millis(),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use entity::sea_orm_active_enums::PushService;
+
+use crate::proto::{self, DeviceReply, NotificationSettingsReply};
+
+impl From<proto::PushService> for PushService {
+    fn from(value: proto::PushService) -> Self {
+        match value {
+            proto::PushService::Ntfy => Self::Ntfy,
+            proto::PushService::Gotify => Self::Gotify,
+        }
+    }
+}
+
+impl From<PushService> for proto::PushService {
+    fn from(value: PushService) -> Self {
+        match value {
+            PushService::Ntfy => Self::Ntfy,
+            PushService::Gotify => Self::Gotify,
+        }
+    }
+}
+
+impl From<entity::notification_setting::Model> for NotificationSettingsReply {
+    fn from(value: entity::notification_setting::Model) -> Self {
+        Self {
+            fcm: value.fcm,
+            email: value.email,
+            webhook_url: value.webhook_url,
+            push_service: proto::PushService::from(value.push_service).into(),
+            push_url: value.push_url,
+            has_push_token: value.push_token.is_some(),
+            quiet_start: value.quiet_start.map(|time| time.format("%H:%M").to_string()),
+            quiet_end: value.quiet_end.map(|time| time.format("%H:%M").to_string()),
+            timezone: value.timezone,
+        }
+    }
+}
+
+impl From<entity::device_token_stat::Model> for DeviceReply {
+    fn from(value: entity::device_token_stat::Model) -> Self {
+        Self {
+            token: value.token,
+            sent: value.sent,
+            failed: value.failed,
+            last_sent_at: value.last_sent_at.map(|date| date.and_utc().timestamp_millis()),
+            last_failed_at: value.last_failed_at.map(|date| date.and_utc().timestamp_millis()),
+            last_error: value.last_error,
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use entity::sea_orm_active_enums::ReadingStatus;
+
+use crate::proto;
+
+impl From<proto::ReadingStatus> for ReadingStatus {
+    fn from(value: proto::ReadingStatus) -> Self {
+        match value {
+            proto::ReadingStatus::Reading => Self::Reading,
+            proto::ReadingStatus::PlanToRead => Self::PlanToRead,
+            proto::ReadingStatus::OnHold => Self::OnHold,
+            proto::ReadingStatus::Dropped => Self::Dropped,
+            proto::ReadingStatus::Completed => Self::Completed,
+        }
+    }
+}
+
+impl From<ReadingStatus> for proto::ReadingStatus {
+    fn from(value: ReadingStatus) -> Self {
+        match value {
+            ReadingStatus::Reading => Self::Reading,
+            ReadingStatus::PlanToRead => Self::PlanToRead,
+            ReadingStatus::OnHold => Self::OnHold,
+            ReadingStatus::Dropped => Self::Dropped,
+            ReadingStatus::Completed => Self::Completed,
+        }
+    }
+}
+
+/// Get the reading status from the i32 that prost uses for enums
+pub fn status_from_i32(value: i32) -> Result<ReadingStatus, tonic::Status> {
+    proto::ReadingStatus::try_from(value)
+        .map(ReadingStatus::from)
+        .map_err(|_| tonic::Status::invalid_argument("Unknown reading status"))
+}
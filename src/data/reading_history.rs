@@ -0,0 +1,43 @@
+use sea_orm::prelude::DateTime;
+use sea_orm::FromQueryResult;
+use tonic::Status;
+
+use crate::proto::ReadingHistoryReply;
+
+#[derive(Debug, FromQueryResult)]
+pub struct Full {
+    pub id: i32,
+    pub manga_id: i32,
+    pub chapter_id: Option<i32>,
+    pub progress: i32,
+    pub read: bool,
+    pub created_at: DateTime,
+
+    // special
+    pub manga_title: String,
+    pub chapter_title: Option<String>,
+    pub chapter_number: Option<f32>,
+}
+
+impl From<Full> for ReadingHistoryReply {
+    fn from(value: Full) -> Self {
+        Self {
+            id: value.id,
+            manga_id: value.manga_id,
+            manga_title: value.manga_title,
+            chapter_id: value.chapter_id,
+            chapter_title: value.chapter_title,
+            chapter_number: value.chapter_number,
+            progress: value.progress,
+            read: value.read,
+            created_at: value.created_at.and_utc().timestamp_millis(),
+        }
+    }
+}
+
+/// Get a date from the milliseconds used by the API
+pub fn date_from_millis(millis: i64) -> Result<DateTime, Status> {
+    chrono::DateTime::from_timestamp_millis(millis)
+        .map(|date| date.naive_utc())
+        .ok_or(Status::invalid_argument("Invalid date"))
+}
@@ -9,8 +9,10 @@ pub struct Full {
     pub permissions: i16,
     pub username: String,
     pub email: String,
+    pub email_verified: bool,
     pub preferred_hostnames: Vec<String>,
     pub device_ids: Vec<String>,
+    pub banned_at: Option<DateTime>,
     pub count_following: i64,
     pub count_followers: i64,
     // pub count_reading: i64,
@@ -27,11 +29,13 @@ impl From<Full> for UserFullReply {
             id: value.id,
             username: value.username,
             email: value.email,
+            email_verified: value.email_verified,
             permissions: value.permissions as i32,
             preferred_hostnames: value.preferred_hostnames,
             device_ids: value.device_ids,
             count_followers: value.count_followers,
             count_following: value.count_following,
+            banned_at: value.banned_at.map(|date| date.and_utc().timestamp_millis()),
             created_at: value.created_at.and_utc().timestamp_millis(),
             updated_at: value.updated_at.and_utc().timestamp_millis(),
         }
@@ -1,16 +1,20 @@
+use chrono::Utc;
 use hmac::{Hmac, Mac};
+use hyper::Uri;
 use jwt::{SignWithKey, VerifyWithKey};
-use sea_orm::EntityTrait;
+use sea_orm::{DatabaseConnection, EntityTrait};
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use tonic::service::Interceptor;
 use tonic::{Request, Status};
 
+use crate::proto::{AuthError, AuthErrorType};
 use crate::util::auth::Authorize;
 use crate::util::db::DatabaseRequest;
+use crate::util::session;
 
 lazy_static! {
-    static ref SECRET_KEY: Hmac<Sha256> = Hmac::new_from_slice(
+    pub static ref SECRET_KEY: Hmac<Sha256> = Hmac::new_from_slice(
         std::env::var("JWT_SECRET")
             .unwrap_or("bUHhhHH#!bU@NkNUnK12".to_string())
             .as_bytes()
@@ -19,14 +23,23 @@ lazy_static! {
 }
 
 /// JWT Token
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Token {
+    /// User ID
     pub id: i32,
+    /// Session ID
+    pub sid: i32,
+    /// Issued at (seconds)
+    pub iat: i64,
+    /// Expires at (seconds)
+    pub exp: i64,
+    /// ID of the refresh token this token was issued with
+    pub jti: i32,
 }
 
 /// Sign JWT Token
-pub fn sign(id: i32) -> Result<String, jwt::Error> {
-    Token { id }.sign_with_key(&SECRET_KEY.clone())
+pub fn sign(id: i32, sid: i32, jti: i32, iat: i64, exp: i64) -> Result<String, jwt::Error> {
+    Token { id, sid, iat, exp, jti }.sign_with_key(&SECRET_KEY.clone())
 }
 
 trait UserHasPermissions {
@@ -35,8 +48,10 @@ trait UserHasPermissions {
 }
 
 impl UserHasPermissions for entity::user::Model {
+    /// Admins are allowed to do everything
     fn has_permission(&self, permission: UserPermissions) -> bool {
-        (UserPermissions::from_bits(self.permissions as u32).unwrap() & permission) == permission
+        let perms = UserPermissions::from_bits_truncate(self.permissions as u32);
+        perms.contains(permission) || perms.contains(UserPermissions::ADMIN)
     }
 }
 
@@ -50,6 +65,36 @@ bitflags! {
     }
 }
 
+/// Check that the session of a token still exists and its user may still log in
+pub async fn verify_session(
+    db: &DatabaseConnection,
+    claims: &Token,
+) -> Result<(entity::session::Model, entity::user::Model), Status> {
+    // The session it was issued for should still exist
+    let session = entity::session::Entity::find_by_id(claims.sid)
+        .one(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .filter(|session| session.user_id == claims.id)
+        .ok_or(AuthError::new(AuthErrorType::TokenRevoked, "Session has been revoked"))?;
+
+    // Get the user from the database with the id stored in the token
+    let user = entity::user::Entity::find_by_id(claims.id)
+        .one(db)
+        .await
+        .map_err(|_e| Status::internal("Bearer token is invalid"))?
+        // If the user was deleted the token is invalid
+        .ok_or(Status::unauthenticated(
+            "User belonging to this token does not exists anymore",
+        ))?;
+
+    if user.banned_at.is_some() {
+        return Err(Status::permission_denied("This account has been banned"));
+    }
+
+    Ok((session, user))
+}
+
 /// Check if user is authenticated
 pub async fn check_auth(mut req: Request<()>) -> Result<Request<()>, Status> {
     let db = req.db()?;
@@ -66,42 +111,91 @@ pub async fn check_auth(mut req: Request<()>) -> Result<Request<()>, Status> {
         // If there is a token
         if let Some(token) = token {
             // Verify it
-            let Token { id: user_id } = token
+            let claims: Token = token
                 .verify_with_key(&SECRET_KEY.clone())
-                .map_err(|e| Status::unauthenticated(e.to_string()))?;
-
-            // Get the user from the database with the id stored in the token
-            let user = entity::user::Entity::find_by_id(user_id)
-                .one(db)
-                .await
-                .map_err(|_e| Status::internal("Bearer token is invalid"))?
-                // If the user was deleted the token is invalid
-                .ok_or(Status::unauthenticated(
-                    "User belonging to this token does not exists anymore",
-                ))?;
-
-            // Store the logged in user in the request extensions
+                .map_err(|e| AuthError::new(AuthErrorType::TokenInvalid, &e.to_string()))?;
+
+            if claims.exp <= Utc::now().timestamp() {
+                return Err(AuthError::new(AuthErrorType::TokenExpired, "Bearer token has expired").into());
+            }
+
+            let (session, user) = verify_session(db, &claims).await?;
+            session::touch(db, &session, req.remote_addr().map(|addr| addr.ip().to_string())).await?;
+
+            // Store the logged in user and its token in the request extensions
             req.extensions_mut().insert(user);
+            req.extensions_mut().insert(claims);
         } else {
             // "authorization" header was set but the token is invalid
-            return Err(Status::unauthenticated("Bearer token is invalid"));
+            return Err(AuthError::new(AuthErrorType::TokenInvalid, "Bearer token is invalid").into());
         }
     }
 
     Ok(req)
 }
 
+/// Permissions that are required to call the methods of a service
+#[derive(Clone, Copy)]
+enum Requirement {
+    /// Every method needs these permissions
+    Service(UserPermissions),
+    /// The methods in the map need permissions and the public methods none, all others are denied
+    Methods {
+        methods: &'static phf::Map<&'static str, UserPermissions>,
+        public: &'static phf::Set<&'static str>,
+    },
+}
+
 /// LoggedInCheck Struct to impl Interceptor for
 #[derive(Clone)]
 pub struct LoggedInCheck {
-    perms: UserPermissions,
+    requirement: Requirement,
 }
 
 /// Implementation
 impl LoggedInCheck {
     /// Create a new instance which checks for the specified perms
     pub fn new(perms: UserPermissions) -> Self {
-        Self { perms }
+        Self {
+            requirement: Requirement::Service(perms),
+        }
+    }
+
+    /// Create a new instance which checks the perms of each method
+    ///
+    /// Methods are named like in the request path (e.g. "CreateMany")
+    pub fn per_method(
+        methods: &'static phf::Map<&'static str, UserPermissions>,
+        public: &'static phf::Set<&'static str>,
+    ) -> Self {
+        Self {
+            requirement: Requirement::Methods { methods, public },
+        }
+    }
+
+    /// Get the permissions required for a request, None if it is public
+    fn required(&self, req: &Request<()>) -> Result<Option<UserPermissions>, Status> {
+        match self.requirement {
+            Requirement::Service(perms) => Ok(Some(perms)),
+            Requirement::Methods { methods, public } => {
+                // Path looks like "/rumgap.v1.Manga/Update"
+                let method = req
+                    .extensions()
+                    .get::<Uri>()
+                    .and_then(|uri| uri.path().rsplit('/').next())
+                    .ok_or(Status::internal("Request path is unknown"))?;
+
+                match methods.get(method) {
+                    Some(perms) => Ok(Some(*perms)),
+                    None if public.contains(method) => Ok(None),
+                    // A method that was forgotten should not be public by accident
+                    None => {
+                        warn!("Method {} is not listed in the permissions of its service", method);
+                        Err(Status::permission_denied("This call is not allowed"))
+                    }
+                }
+            }
+        }
     }
 }
 
@@ -114,11 +208,15 @@ impl Interceptor for LoggedInCheck {
     /// If the user is not logged in or is missing permissions
     /// an error will be returned (Status)
     fn call(&mut self, req: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
+        let Some(perms) = self.required(&req)? else {
+            return Ok(req);
+        };
+
         let user = req.authorize().ok();
 
         match user {
             Some(user) => {
-                if user.has_permission(self.perms) {
+                if user.has_permission(perms) {
                     Ok(req)
                 } else {
                     Err(Status::permission_denied(
@@ -132,3 +230,22 @@ impl Interceptor for LoggedInCheck {
         }
     }
 }
+
+#[test]
+fn unlisted_methods_are_denied() {
+    static METHODS: phf::Map<&'static str, UserPermissions> = phf_map! {
+        "Update" => UserPermissions::MOD,
+    };
+    static PUBLIC: phf::Set<&'static str> = phf_set! { "Get" };
+
+    let check = LoggedInCheck::per_method(&METHODS, &PUBLIC);
+    let required = |path: &'static str| {
+        let mut req = Request::new(());
+        req.extensions_mut().insert(Uri::from_static(path));
+        check.required(&req).map_err(|e| e.code())
+    };
+
+    assert_eq!(required("/rumgap.v1.Manga/Update"), Ok(Some(UserPermissions::MOD)));
+    assert_eq!(required("/rumgap.v1.Manga/Get"), Ok(None));
+    assert_eq!(required("/rumgap.v1.Manga/Health"), Err(tonic::Code::PermissionDenied));
+}
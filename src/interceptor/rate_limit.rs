@@ -0,0 +1,40 @@
+use hyper::Uri;
+use tonic::service::Interceptor;
+use tonic::{Request, Status};
+
+use crate::util::rate_limit::{exhausted, ADDRESS_LIMITER};
+
+/// Methods that are rate limited per remote address
+static LIMITED_METHODS: phf::Set<&'static str> = phf_set! {
+    "/rumgap.v1.User/Login",
+    "/rumgap.v1.User/Register",
+    "/rumgap.v1.User/Refresh",
+    "/rumgap.v1.User/RequestPasswordReset",
+    "/rumgap.v1.User/ResetPassword",
+};
+
+/// RateLimitCheck Struct to impl Interceptor for
+#[derive(Clone, Default)]
+pub struct RateLimitCheck;
+
+/// Implement tonic's Interceptor for RateLimitCheck
+impl Interceptor for RateLimitCheck {
+    /// When a request is made to a limited method
+    /// a token will be taken from the bucket of the remote address
+    ///
+    /// If the bucket is empty a resource exhausted error will be returned (Status)
+    fn call(&mut self, req: Request<()>) -> Result<Request<()>, Status> {
+        let limited = req
+            .extensions()
+            .get::<Uri>()
+            .is_some_and(|uri| LIMITED_METHODS.contains(uri.path()));
+
+        if let (true, Some(addr)) = (limited, req.remote_addr()) {
+            ADDRESS_LIMITER
+                .check(addr.ip())
+                .map_err(|retry_after| exhausted("Too many requests, try again later", retry_after))?;
+        }
+
+        Ok(req)
+    }
+}
@@ -10,6 +10,7 @@ extern crate phf;
 use std::env;
 
 use crate::util::auth::Authorize;
+use crate::util::polite_scraper::PoliteScraper;
 use hyper::Uri;
 use manga_parser::scraper::scraper_manager::ScraperManager;
 use migration::{DbErr, Migrator, MigratorTrait};
@@ -25,7 +26,7 @@ mod service;
 mod util;
 
 lazy_static! {
-    static ref MANGA_PARSER: ScraperManager = manga_parser::scraper::scraper_manager::ScraperManager::default();
+    static ref MANGA_PARSER: PoliteScraper = PoliteScraper::new(ScraperManager::default());
 }
 
 /// Load all ProtoBuf files
@@ -52,6 +53,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let server_url = format!("{host}:{port}");
     let addr = server_url.parse()?;
 
+    // Fail now instead of when the first mail is sent
+    crate::util::mailer::check()?;
+
     // Establish connection to database and apply migrations
     let conn = conn_db(&db_url).await.unwrap();
 
@@ -63,7 +67,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         crate::util::updater::watch_updates(&cloned_conn).await;
     });
 
+    // Start sending digests after quiet hours
+    let cloned_conn = conn.clone();
+    tokio::spawn(async move {
+        crate::util::notifier::digest::watch_digests(&cloned_conn).await;
+    });
+
     Server::builder()
+        .layer(tower::util::MapRequestLayer::new(inject_uri))
+        .layer(tonic::service::InterceptorLayer::new(
+            interceptor::rate_limit::RateLimitCheck,
+        ))
         .layer(tonic::service::InterceptorLayer::new(move |req| {
             inject_db(req, conn.clone())
         }))
@@ -76,6 +90,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .add_service(service::v1::reading::server())
         .add_service(service::v1::search::server())
         .add_service(service::v1::meta::server())
+        .add_service(service::v1::admin::server())
+        .add_service(service::v1::collection::server())
+        .add_service(service::v1::notification::server())
         .add_service(
             Builder::configure()
                 .register_encoded_file_descriptor_set(proto::FILE_DESCRIPTOR_SET)
@@ -105,6 +122,14 @@ fn inject_db(mut req: Request<()>, conn: DatabaseConnection) -> Result<Request<(
     Ok(req)
 }
 
+/// Add the request URI to the extensions so interceptors know which method is called
+fn inject_uri(mut req: hyper::Request<tonic::body::Body>) -> hyper::Request<tonic::body::Body> {
+    let uri = req.uri().clone();
+    req.extensions_mut().insert(uri);
+
+    req
+}
+
 /// Log the incoming request
 async fn logger(req: Request<()>) -> Result<Request<()>, Status> {
     let logged_in = req.authorize().ok();
@@ -129,6 +154,31 @@ macro_rules! export_service {
                 .accept_compressed(tonic::codec::CompressionEncoding::Gzip)
         }
     };
+    ($server:ident, $server_handler:ident, auth = { $($method:tt => $auth:expr),* $(,)? } $(, public = [ $($public:tt),* $(,)? ])?) => {
+        /// Permissions required per method
+        static METHOD_PERMISSIONS: phf::Map<&'static str, $crate::interceptor::auth::UserPermissions> = phf_map! {
+            $($method => $auth),*
+        };
+        /// Methods anyone can call, methods that are neither listed here nor above are denied
+        static PUBLIC_METHODS: phf::Set<&'static str> = phf_set! {
+            $($($public),*)?
+        };
+
+        pub fn server() -> tonic::service::interceptor::InterceptedService<
+            $server<$server_handler>,
+            $crate::interceptor::auth::LoggedInCheck,
+        > {
+            tower::ServiceBuilder::new()
+                .layer(tonic::service::InterceptorLayer::new(
+                    crate::interceptor::auth::LoggedInCheck::per_method(&METHOD_PERMISSIONS, &PUBLIC_METHODS),
+                ))
+                .service(
+                    $server::new($server_handler::default())
+                        .send_compressed(tonic::codec::CompressionEncoding::Gzip)
+                        .accept_compressed(tonic::codec::CompressionEncoding::Gzip),
+                )
+        }
+    };
     ($server:ident, $server_handler:ident, auth = $auth:expr) => {
         pub fn server() -> tonic::service::interceptor::InterceptedService<
             $server<$server_handler>,
@@ -136,7 +186,7 @@ macro_rules! export_service {
         > {
             tower::ServiceBuilder::new()
                 .layer(tonic::service::InterceptorLayer::new(
-                    crate::interceptor::auth::LoggedInCheck::new(UserPermissions::USER),
+                    crate::interceptor::auth::LoggedInCheck::new($auth),
                 ))
                 .service(
                     $server::new($server_handler::default())
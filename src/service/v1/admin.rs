@@ -0,0 +1,403 @@
+use chrono::Utc;
+use manga_parser::Url;
+use sea_orm::ActiveValue::{Set, Unchanged};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait, PaginatorTrait,
+    QueryFilter, QueryOrder, QueryTrait, Statement, TransactionTrait,
+};
+use serde_json::json;
+use tonic::{Request, Response, Status};
+
+use super::manga::{get_manga_by_id, save_manga};
+use super::user::get_user_by_id;
+use crate::interceptor::auth::UserPermissions;
+use crate::proto::admin_server::{Admin, AdminServer};
+use crate::proto::{
+    AuditLogReply, AuditLogsReply, BanRequest, Empty, Id, ImageCacheReply, MangaReply, MergeMangaRequest,
+    PaginateQuery, PaginateReply, SetPermissionsRequest, UserFullReply,
+};
+use crate::util::auth::Authorize;
+use crate::util::db::DatabaseRequest;
+use crate::util::image_proxy::IMAGE_CACHE;
+use crate::util::{audit, chapter_read};
+
+/// Move readings to the target manga, keeping the highest progress and muting or limiting notifications
+/// if either reading did
+const MERGE_READING_QUERY: &str = r#"
+INSERT INTO reading (user_id, manga_id, progress, status, muted, notify_caught_up)
+SELECT user_id, $2, progress, status, muted, notify_caught_up FROM reading WHERE manga_id = $1
+ON CONFLICT (user_id, manga_id) DO UPDATE SET
+    progress = GREATEST(reading.progress, EXCLUDED.progress),
+    muted = reading.muted OR EXCLUDED.muted,
+    notify_caught_up = reading.notify_caught_up OR EXCLUDED.notify_caught_up"#;
+
+/// Move chapter offsets to the chapter with the same number in the target manga
+const MERGE_CHAPTER_OFFSET_QUERY: &str = r#"
+INSERT INTO chapter_offset (user_id, chapter_id, "offset", page)
+SELECT chapter_offset.user_id, target.id, chapter_offset."offset", chapter_offset.page
+FROM chapter_offset
+JOIN chapter source ON source.id = chapter_offset.chapter_id AND source.manga_id = $1
+JOIN chapter target ON target.manga_id = $2 AND target.number = source.number AND target.deleted_at IS NULL
+ON CONFLICT (user_id, chapter_id) DO NOTHING"#;
+
+/// Move read chapters to the chapter with the same number in the target manga
+const MERGE_CHAPTER_READ_QUERY: &str = r#"
+INSERT INTO chapter_read (user_id, chapter_id)
+SELECT chapter_read.user_id, target.id
+FROM chapter_read
+JOIN chapter source ON source.id = chapter_read.chapter_id AND source.manga_id = $1
+JOIN chapter target ON target.manga_id = $2 AND target.number = source.number AND target.deleted_at IS NULL
+ON CONFLICT (user_id, chapter_id) DO NOTHING"#;
+
+/// Move reading history to the target manga, chapters become the chapter with the same number in the target
+const MERGE_READING_HISTORY_QUERY: &str = r#"
+UPDATE reading_history SET manga_id = $2, chapter_id = (
+    SELECT target.id
+    FROM chapter source
+    JOIN chapter target ON target.manga_id = $2 AND target.number = source.number AND target.deleted_at IS NULL
+    WHERE source.id = reading_history.chapter_id
+    ORDER BY target.position
+    LIMIT 1
+)
+WHERE manga_id = $1"#;
+
+/// Move collection entries to the target manga, keeping the position in the collection
+const MERGE_COLLECTION_QUERY: &str = r#"
+INSERT INTO collection_manga (collection_id, manga_id, position)
+SELECT collection_id, $2, position FROM collection_manga WHERE manga_id = $1
+ON CONFLICT DO NOTHING"#;
+
+/// Statements that move everything of the source manga to the target and delete the source
+fn merge_statements(source_id: i32, target_id: i32) -> Vec<Statement> {
+    let mut statements: Vec<Statement> = [
+        MERGE_READING_QUERY,
+        MERGE_CHAPTER_OFFSET_QUERY,
+        MERGE_CHAPTER_READ_QUERY,
+        MERGE_READING_HISTORY_QUERY,
+        MERGE_COLLECTION_QUERY,
+    ]
+    .into_iter()
+    .map(|query| Statement::from_sql_and_values(DbBackend::Postgres, query, [source_id.into(), target_id.into()]))
+    .collect();
+
+    // Everything that still points to the source is deleted with it
+    statements.push(entity::manga::Entity::delete_by_id(source_id).build(DbBackend::Postgres));
+    statements
+}
+
+/// Admins should not be able to lock themselves out
+fn not_self(logged_in: &entity::user::Model, user_id: i32) -> Result<(), Status> {
+    if logged_in.id == user_id {
+        Err(Status::failed_precondition("You can not do this to yourself"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Get a user by their ID
+async fn find_user(db: &DatabaseConnection, user_id: i32) -> Result<entity::user::Model, Status> {
+    entity::user::Entity::find_by_id(user_id)
+        .one(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .ok_or(Status::not_found("User not found"))
+}
+
+/// Get a manga by its ID
+async fn find_manga(db: &DatabaseConnection, manga_id: i32) -> Result<entity::manga::Model, Status> {
+    entity::manga::Entity::find_by_id(manga_id)
+        .one(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .ok_or(Status::not_found("Manga not found"))
+}
+
+#[derive(Debug, Default)]
+pub struct AdminController;
+
+#[tonic::async_trait]
+impl Admin for AdminController {
+    /// Change the permissions of a user
+    async fn set_permissions(
+        &self,
+        request: Request<SetPermissionsRequest>,
+    ) -> Result<Response<UserFullReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        not_self(logged_in, req.user_id)?;
+        let permissions = UserPermissions::from_bits(req.permissions as u32)
+            .ok_or(Status::invalid_argument("Unknown permission bits"))?;
+        let user = find_user(db, req.user_id).await?;
+
+        entity::user::ActiveModel {
+            id: Unchanged(user.id),
+            permissions: Set(permissions.bits() as i16),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+        audit::record(
+            db,
+            logged_in,
+            "set_permissions",
+            user.id,
+            json!({ "from": user.permissions, "to": permissions.bits() }),
+        )
+        .await?;
+
+        Ok(Response::new(get_user_by_id(db, user.id).await?.into()))
+    }
+
+    /// Ban a user and end all their sessions
+    async fn ban(&self, request: Request<BanRequest>) -> Result<Response<UserFullReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        not_self(logged_in, req.user_id)?;
+        let user = find_user(db, req.user_id).await?;
+
+        entity::user::ActiveModel {
+            id: Unchanged(user.id),
+            banned_at: Set(Some(Utc::now().naive_utc())),
+            ban_reason: Set(req.reason.clone()),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+        entity::session::Entity::delete_many()
+            .filter(entity::session::Column::UserId.eq(user.id))
+            .exec(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        audit::record(db, logged_in, "ban", user.id, json!({ "reason": req.reason })).await?;
+
+        Ok(Response::new(get_user_by_id(db, user.id).await?.into()))
+    }
+
+    /// Lift the ban of a user
+    async fn unban(&self, request: Request<Id>) -> Result<Response<UserFullReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        let user = find_user(db, req.id).await?;
+
+        entity::user::ActiveModel {
+            id: Unchanged(user.id),
+            banned_at: Set(None),
+            ban_reason: Set(None),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+        audit::record(db, logged_in, "unban", user.id, json!({ "reason": user.ban_reason })).await?;
+
+        Ok(Response::new(get_user_by_id(db, user.id).await?.into()))
+    }
+
+    /// Delete a user and everything that belongs to them
+    async fn delete_user(&self, request: Request<Id>) -> Result<Response<Empty>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        not_self(logged_in, req.id)?;
+        let user = find_user(db, req.id).await?;
+
+        entity::user::Entity::delete_by_id(user.id)
+            .exec(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        audit::record(
+            db,
+            logged_in,
+            "delete_user",
+            user.id,
+            json!({ "username": user.username, "email": user.email }),
+        )
+        .await?;
+
+        Ok(Response::new(Empty::default()))
+    }
+
+    /// Delete a manga with all its chapters and readings
+    async fn delete_manga(&self, request: Request<Id>) -> Result<Response<Empty>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        let manga = find_manga(db, req.id).await?;
+
+        entity::manga::Entity::delete_by_id(manga.id)
+            .exec(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        audit::record(
+            db,
+            logged_in,
+            "delete_manga",
+            manga.id,
+            json!({ "title": manga.title, "url": manga.url }),
+        )
+        .await?;
+
+        Ok(Response::new(Empty::default()))
+    }
+
+    /// Merge a duplicate manga into another one
+    ///
+    /// Readers, chapter offsets, reading history and collection entries are moved to the target, then the source is deleted
+    async fn merge_manga(&self, request: Request<MergeMangaRequest>) -> Result<Response<MangaReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        if req.source_id == req.target_id {
+            return Err(Status::invalid_argument("Can not merge a manga into itself"));
+        }
+
+        let source = find_manga(db, req.source_id).await?;
+        let target = find_manga(db, req.target_id).await?;
+
+        let txn = db.begin().await.map_err(|e| Status::internal(e.to_string()))?;
+        for statement in merge_statements(source.id, target.id) {
+            txn.execute(statement)
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?;
+        }
+        chapter_read::sync_progress(&txn, target.id).await?;
+        txn.commit().await.map_err(|e| Status::internal(e.to_string()))?;
+
+        audit::record(
+            db,
+            logged_in,
+            "merge_manga",
+            target.id,
+            json!({ "source_id": source.id, "source_title": source.title, "source_url": source.url }),
+        )
+        .await?;
+
+        Ok(Response::new(get_manga_by_id(db, Some(logged_in), target.id).await?))
+    }
+
+    /// Force a manga to be scraped again
+    async fn rescrape(&self, request: Request<Id>) -> Result<Response<MangaReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        let manga = find_manga(db, req.id).await?;
+        let url = Url::parse(&manga.url).map_err(|e| Status::internal(e.to_string()))?;
+
+        info!("Rescraping manga with id '{}' [{}]", manga.id, manga.url);
+        let reply = save_manga(db, Some(logged_in), Some(manga.id), url).await?;
+
+        audit::record(db, logged_in, "rescrape", manga.id, json!({ "url": manga.url })).await?;
+
+        Ok(Response::new(reply))
+    }
+
+    /// Get paginated audit log entries, newest first
+    async fn audit_log(&self, request: Request<PaginateQuery>) -> Result<Response<AuditLogsReply>, Status> {
+        let db = request.db()?;
+        let req = request.get_ref();
+        let per_page = req.per_page.unwrap_or(10).clamp(1, 50);
+        let paginate = entity::audit_log::Entity::find()
+            .order_by_desc(entity::audit_log::Column::Id)
+            .paginate(db, per_page);
+
+        // Get max page and total items
+        let amount = paginate
+            .num_items_and_pages()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let max_page = if amount.number_of_pages == 0 {
+            0
+        } else {
+            amount.number_of_pages - 1
+        };
+
+        let page = req.page.unwrap_or(0).clamp(0, max_page);
+
+        // Get items from page
+        let items = paginate
+            .fetch_page(page)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(AuditLogsReply {
+            pagination: Some(PaginateReply {
+                page,
+                per_page,
+                max_page,
+                total: amount.number_of_items,
+            }),
+            items: items
+                .into_iter()
+                .map(|entry| AuditLogReply {
+                    id: entry.id,
+                    actor_id: entry.actor_id,
+                    action: entry.action,
+                    target_id: entry.target_id,
+                    details: entry.details.to_string(),
+                    created_at: entry.created_at.and_utc().timestamp_millis(),
+                })
+                .collect(),
+        }))
+    }
+
+    /// Get hits, misses and size of the image proxy cache
+    async fn image_cache(&self, _request: Request<Empty>) -> Result<Response<ImageCacheReply>, Status> {
+        Ok(Response::new(IMAGE_CACHE.stats()))
+    }
+}
+
+crate::export_service!(AdminServer, AdminController, auth = UserPermissions::ADMIN);
+
+#[test]
+fn merge_keeps_history() {
+    let statements = merge_statements(1, 2);
+    let history = statements
+        .iter()
+        .position(|statement| statement.sql.contains("UPDATE reading_history"))
+        .unwrap();
+
+    // History points to the target before the source and its history are deleted
+    assert_eq!(
+        statements[history].values,
+        Some(sea_orm::Values(vec![1.into(), 2.into()]))
+    );
+    assert!(history < statements.len() - 1);
+}
+
+#[test]
+fn merge_keeps_collections() {
+    let statements = merge_statements(1, 2);
+    let collection = statements
+        .iter()
+        .position(|statement| statement.sql.contains("INSERT INTO collection_manga"))
+        .unwrap();
+
+    // Collection entries are copied to the target before the source and its entries are deleted
+    assert_eq!(
+        statements[collection].values,
+        Some(sea_orm::Values(vec![1.into(), 2.into()]))
+    );
+    assert!(statements
+        .last()
+        .is_some_and(|statement| statement.sql.starts_with(r#"DELETE FROM "manga""#)));
+    assert!(collection < statements.len() - 1);
+}
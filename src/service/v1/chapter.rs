@@ -1,25 +1,58 @@
 use std::num::TryFromIntError;
+use std::pin::Pin;
+
+use futures::Stream;
 
-use manga_parser::scraper::MangaScraper;
 use manga_parser::Url;
 use migration::{Expr, IntoCondition, JoinType};
 use sea_orm::{
-    ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait, RelationTrait,
+    ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait,
+    RelationTrait,
 };
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status};
 
+use crate::data;
+use crate::interceptor::auth::UserPermissions;
 use crate::proto::chapter_server::{Chapter, ChapterServer};
-use crate::proto::{ChapterReply, ChapterRequest, ChaptersReply, Id, ImagesReply, PaginateChapterQuery, PaginateReply};
+use crate::proto::{
+    ChapterReply, ChapterRequest, ChaptersReply, Id, ImageChunk, ImageRequest, ImagesReply, PageChunk,
+    PaginateChapterQuery, PaginateReply,
+};
 use crate::util::auth::Authorize;
+use crate::util::cbz::Page;
+use crate::util::chapter_image;
 use crate::util::db::DatabaseRequest;
-use crate::util::scrape_error_proto::StatusWrapper;
-use crate::{data, MANGA_PARSER};
+use crate::util::image_proxy::{self, IMAGE_CACHE};
+
+const IMAGE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Load a page of a chapter through the image cache
+pub async fn download_page(chapter: &entity::chapter::Model, url: &Url) -> Result<Page, Status> {
+    let (data, content_type) = IMAGE_CACHE.get(url.as_str(), &chapter.url).await?;
+
+    Ok(Page { data, content_type })
+}
+
+/// Load all pages of a chapter in order
+pub async fn download_pages(db: &DatabaseConnection, chapter: &entity::chapter::Model) -> Result<Vec<Page>, Status> {
+    let mut pages = vec![];
+    for url in chapter_image::images(db, chapter).await? {
+        pages.push(download_page(chapter, &url).await?);
+    }
+
+    Ok(pages)
+}
 
 #[derive(Debug, Default)]
 pub struct ChapterController;
 
 #[tonic::async_trait]
 impl Chapter for ChapterController {
+    type ImageStream = Pin<Box<dyn Stream<Item = Result<ImageChunk, Status>> + Send>>;
+    type DownloadStream = Pin<Box<dyn Stream<Item = Result<PageChunk, Status>> + Send>>;
+
     /// Get chapter images
     async fn images(&self, request: Request<Id>) -> Result<Response<ImagesReply>, Status> {
         let db = request.db()?;
@@ -28,24 +61,98 @@ impl Chapter for ChapterController {
 
         // Get chapter
         let chapter = entity::chapter::Entity::find_by_id(chapter_id)
+            .filter(entity::chapter::Column::DeletedAt.is_null())
             .one(db)
             .await
             .map_err(|e| Status::internal(e.to_string()))?
             .ok_or(Status::not_found("Chapter not found"))?;
 
         // Get images
-        let images = MANGA_PARSER
-            .chapter_images(&Url::parse(&chapter.url).unwrap())
-            .await
-            .map_err(StatusWrapper::from)?;
+        let images = chapter_image::images(db, &chapter).await?;
 
         debug!("{} images found in {}", images.len(), chapter.url);
 
         Ok(Response::new(ImagesReply {
+            proxied: images
+                .iter()
+                .map(|url| image_proxy::sign(url.as_str(), &chapter.url))
+                .collect(),
             items: images.into_iter().map(|url| url.to_string()).collect(),
         }))
     }
 
+    /// Load an image through the image proxy
+    async fn image(&self, request: Request<ImageRequest>) -> Result<Response<Self::ImageStream>, Status> {
+        let req = request.get_ref();
+        image_proxy::verify(req)?;
+
+        let (bytes, content_type) = IMAGE_CACHE.get(&req.url, &req.referer).await?;
+        let size = bytes.len() as u64;
+
+        let chunks: Vec<Result<ImageChunk, Status>> = bytes
+            .chunks(IMAGE_CHUNK_SIZE)
+            .enumerate()
+            .map(|(index, chunk)| {
+                Ok(ImageChunk {
+                    data: chunk.to_vec(),
+                    // Only the first chunk says what the image is
+                    content_type: if index == 0 {
+                        content_type.to_string()
+                    } else {
+                        String::new()
+                    },
+                    size,
+                })
+            })
+            .collect();
+
+        Ok(Response::new(Box::pin(tokio_stream::iter(chunks)) as Self::ImageStream))
+    }
+
+    /// Download all pages of a chapter, in order and split in chunks
+    async fn download(&self, request: Request<Id>) -> Result<Response<Self::DownloadStream>, Status> {
+        let db = request.db()?;
+        let req = request.get_ref();
+
+        let chapter = entity::chapter::Entity::find_by_id(req.id)
+            .filter(entity::chapter::Column::DeletedAt.is_null())
+            .one(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or(Status::not_found("Chapter not found"))?;
+
+        let images = chapter_image::images(db, &chapter).await?;
+
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            for (index, url) in images.iter().enumerate() {
+                let page = match download_page(&chapter, url).await {
+                    Ok(page) => page,
+                    Err(e) => {
+                        let _ = tx.send(Err(e)).await;
+                        return;
+                    }
+                };
+
+                let size = page.data.len() as u64;
+                for data in page.data.chunks(IMAGE_CHUNK_SIZE) {
+                    let chunk = PageChunk {
+                        index: index as i32,
+                        content_type: page.content_type.to_string(),
+                        size,
+                        data: data.to_vec(),
+                    };
+                    if tx.send(Ok(chunk)).await.is_err() {
+                        // Client disconnected
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx)) as Self::DownloadStream))
+    }
+
     /// Get chapter
     async fn get(&self, request: Request<ChapterRequest>) -> Result<Response<ChapterReply>, Status> {
         let db = request.db()?;
@@ -55,6 +162,7 @@ impl Chapter for ChapterController {
 
         let total_chapters = entity::chapter::Entity::find()
             .filter(entity::chapter::Column::MangaId.eq(manga_id))
+            .filter(entity::chapter::Column::DeletedAt.is_null())
             .count(db)
             .await
             .map_err(|e| Status::internal(e.to_string()))?;
@@ -69,8 +177,9 @@ impl Chapter for ChapterController {
 
         // Get chapter
         let chapter = entity::chapter::Entity::find()
-            .order_by(entity::chapter::Column::Id, migration::Order::Asc)
+            .order_by(entity::chapter::Column::Position, migration::Order::Asc)
             .filter(entity::chapter::Column::MangaId.eq(manga_id))
+            .filter(entity::chapter::Column::DeletedAt.is_null())
             .offset(offset)
             .column_as(Expr::cust("null"), "offset")
             .column_as(Expr::cust("null"), "page")
@@ -118,7 +227,8 @@ impl Chapter for ChapterController {
         // Create paginate object
         let paginate = entity::chapter::Entity::find()
             .filter(entity::chapter::Column::MangaId.eq(manga_id))
-            .order_by(entity::chapter::Column::Id, order)
+            .filter(entity::chapter::Column::DeletedAt.is_null())
+            .order_by(entity::chapter::Column::Position, order)
             .column_as(Expr::cust("null"), "offset")
             .column_as(Expr::cust("null"), "page")
             .apply_if(logged_in, |query, logged_in| {
@@ -183,4 +293,11 @@ impl Chapter for ChapterController {
     }
 }
 
-crate::export_service!(ChapterServer, ChapterController);
+crate::export_service!(
+    ChapterServer,
+    ChapterController,
+    auth = {
+        "Download" => UserPermissions::USER,
+    },
+    public = ["Get", "Index", "Images", "Image"]
+);
@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+
+use migration::JoinType;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, RelationTrait, Select, TransactionTrait,
+};
+use tonic::{Request, Response, Status};
+
+use super::manga::index_manga;
+use crate::data;
+use crate::interceptor::auth::UserPermissions;
+use crate::proto::collection_server::{Collection, CollectionServer};
+use crate::proto::{
+    CollectionMangaRequest, CollectionReorderRequest, CollectionReply, CollectionRequest, CollectionUpdateRequest,
+    CollectionsReply, Empty, Id, MangasReply, PaginateCollectionQuery, PaginateQuery, PaginateReply,
+};
+use crate::util::auth::Authorize;
+use crate::util::db::DatabaseRequest;
+use crate::util::verify;
+
+/// Collections of a user with the amount of manga in them
+fn index_collection(user_id: i32) -> Select<entity::collection::Entity> {
+    entity::collection::Entity::find()
+        .filter(entity::collection::Column::UserId.eq(user_id))
+        .left_join(entity::collection_manga::Entity)
+        .column_as(entity::collection_manga::Column::MangaId.count(), "count_manga")
+        .group_by(entity::collection::Column::Id)
+}
+
+/// Get a "full" collection of a user by its ID
+async fn get_collection_by_id(
+    db: &DatabaseConnection,
+    user_id: i32,
+    collection_id: i32,
+) -> Result<CollectionReply, Status> {
+    let collection = index_collection(user_id)
+        .filter(entity::collection::Column::Id.eq(collection_id))
+        .into_model::<data::collection::Full>()
+        .one(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .ok_or(Status::not_found("Collection not found"))?;
+
+    Ok(collection.into())
+}
+
+/// Get a collection by its ID, only if it belongs to the user
+async fn find_collection(
+    db: &DatabaseConnection,
+    user_id: i32,
+    collection_id: i32,
+) -> Result<entity::collection::Model, Status> {
+    entity::collection::Entity::find_by_id(collection_id)
+        .filter(entity::collection::Column::UserId.eq(user_id))
+        .one(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .ok_or(Status::not_found("Collection not found"))
+}
+
+/// Map a failed insert or update of a collection to a status
+fn collection_error(err: sea_orm::DbErr) -> Status {
+    if verify::is_conflict(&err) {
+        Status::already_exists("Collection with this name already exists")
+    } else {
+        Status::internal(err.to_string())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CollectionController;
+
+#[tonic::async_trait]
+impl Collection for CollectionController {
+    /// Create a new collection
+    async fn create(&self, request: Request<CollectionRequest>) -> Result<Response<CollectionReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        let collection = entity::collection::ActiveModel {
+            user_id: Set(logged_in.id),
+            name: Set(verify::collection_name(&req.name)?),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .map_err(collection_error)?;
+
+        Ok(Response::new(
+            get_collection_by_id(db, logged_in.id, collection.id).await?,
+        ))
+    }
+
+    /// Get one collection
+    async fn get(&self, request: Request<Id>) -> Result<Response<CollectionReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        Ok(Response::new(get_collection_by_id(db, logged_in.id, req.id).await?))
+    }
+
+    /// Rename a collection
+    async fn update(&self, request: Request<CollectionUpdateRequest>) -> Result<Response<CollectionReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        let mut collection = find_collection(db, logged_in.id, req.id).await?.into_active_model();
+        collection.name = Set(verify::collection_name(&req.name)?);
+        let collection = collection.update(db).await.map_err(collection_error)?;
+
+        Ok(Response::new(
+            get_collection_by_id(db, logged_in.id, collection.id).await?,
+        ))
+    }
+
+    /// Delete a collection, the manga stay on the reading list
+    async fn delete(&self, request: Request<Id>) -> Result<Response<Empty>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        let res = entity::collection::Entity::delete_many()
+            .filter(entity::collection::Column::Id.eq(req.id))
+            .filter(entity::collection::Column::UserId.eq(logged_in.id))
+            .exec(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        if res.rows_affected == 0 {
+            Err(Status::not_found("Collection not found"))
+        } else {
+            Ok(Response::new(Empty::default()))
+        }
+    }
+
+    /// Paginate the collections of the logged in user
+    async fn index(&self, request: Request<PaginateQuery>) -> Result<Response<CollectionsReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+        let per_page = req.per_page.unwrap_or(10).clamp(1, 50);
+
+        let paginate = index_collection(logged_in.id)
+            .order_by_asc(entity::collection::Column::Name)
+            .into_model::<data::collection::Full>()
+            .paginate(db, per_page);
+
+        // Get max page and total items
+        let amount = paginate
+            .num_items_and_pages()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let max_page = if amount.number_of_pages == 0 {
+            0
+        } else {
+            amount.number_of_pages - 1
+        };
+
+        let page = req.page.unwrap_or(0).clamp(0, max_page);
+
+        // Get items from page
+        let items = paginate
+            .fetch_page(page)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(CollectionsReply {
+            pagination: Some(PaginateReply {
+                page,
+                per_page,
+                max_page,
+                total: amount.number_of_items,
+            }),
+            items: items.into_iter().map(|collection| collection.into()).collect(),
+        }))
+    }
+
+    /// Add a manga from the reading list to the end of a collection
+    async fn add_manga(&self, request: Request<CollectionMangaRequest>) -> Result<Response<CollectionReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        let collection = find_collection(db, logged_in.id, req.collection_id).await?;
+
+        entity::reading::Entity::find_by_id((logged_in.id, req.manga_id))
+            .one(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or(Status::not_found("Manga is not on your reading list"))?;
+
+        let last_position: Option<i32> = entity::collection_manga::Entity::find()
+            .select_only()
+            .column_as(entity::collection_manga::Column::Position.max(), "position")
+            .filter(entity::collection_manga::Column::CollectionId.eq(collection.id))
+            .into_tuple()
+            .one(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .flatten();
+
+        entity::collection_manga::ActiveModel {
+            collection_id: Set(collection.id),
+            manga_id: Set(req.manga_id),
+            position: Set(last_position.map_or(0, |position| position + 1)),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .map_err(|e| {
+            if verify::is_conflict(&e) {
+                return Status::already_exists("Manga is already in this collection");
+            }
+            Status::internal(e.to_string())
+        })?;
+
+        Ok(Response::new(
+            get_collection_by_id(db, logged_in.id, collection.id).await?,
+        ))
+    }
+
+    /// Remove a manga from a collection
+    async fn remove_manga(
+        &self,
+        request: Request<CollectionMangaRequest>,
+    ) -> Result<Response<CollectionReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        let collection = find_collection(db, logged_in.id, req.collection_id).await?;
+
+        let res = entity::collection_manga::Entity::delete_by_id((collection.id, req.manga_id))
+            .exec(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        if res.rows_affected == 0 {
+            return Err(Status::not_found("Manga is not in this collection"));
+        }
+
+        Ok(Response::new(
+            get_collection_by_id(db, logged_in.id, collection.id).await?,
+        ))
+    }
+
+    /// Change the order of the manga in a collection
+    ///
+    /// The given manga are moved to the front in the given order, the others keep their relative order after them
+    async fn reorder(&self, request: Request<CollectionReorderRequest>) -> Result<Response<Empty>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        let collection = find_collection(db, logged_in.id, req.collection_id).await?;
+
+        let mut entries: HashMap<i32, entity::collection_manga::Model> = entity::collection_manga::Entity::find()
+            .filter(entity::collection_manga::Column::CollectionId.eq(collection.id))
+            .order_by_asc(entity::collection_manga::Column::Position)
+            .all(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .into_iter()
+            .map(|entry| (entry.manga_id, entry))
+            .collect();
+
+        let mut ordered = vec![];
+        for manga_id in &req.manga_ids {
+            let entry = entries.remove(manga_id).ok_or(Status::invalid_argument(format!(
+                "Manga {manga_id} is not in this collection"
+            )))?;
+            ordered.push(entry);
+        }
+        let mut rest: Vec<_> = entries.into_values().collect();
+        rest.sort_by_key(|entry| entry.position);
+        ordered.extend(rest);
+
+        let txn = db.begin().await.map_err(|e| Status::internal(e.to_string()))?;
+        for (position, entry) in ordered.into_iter().enumerate() {
+            let position = position as i32;
+            if entry.position != position {
+                let mut entry = entry.into_active_model();
+                entry.position = Set(position);
+                entry.update(&txn).await.map_err(|e| Status::internal(e.to_string()))?;
+            }
+        }
+        txn.commit().await.map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(Empty::default()))
+    }
+
+    /// Paginate the manga in a collection in their manual order
+    async fn manga(&self, request: Request<PaginateCollectionQuery>) -> Result<Response<MangasReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        let collection = find_collection(db, logged_in.id, req.id).await?;
+
+        let req = req.paginate_query.unwrap_or_default();
+        let per_page = req.per_page.unwrap_or(10).clamp(1, 50);
+
+        let paginate = index_manga(Some(logged_in.clone()))
+            .join(
+                JoinType::InnerJoin,
+                entity::collection_manga::Relation::Manga.def().rev(),
+            )
+            .filter(entity::collection_manga::Column::CollectionId.eq(collection.id))
+            .group_by(entity::collection_manga::Column::Position)
+            .order_by_asc(entity::collection_manga::Column::Position)
+            .into_model::<data::manga::Full>()
+            .paginate(db, per_page);
+
+        // Get max page and total items
+        let amount = paginate
+            .num_items_and_pages()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let max_page = if amount.number_of_pages == 0 {
+            0
+        } else {
+            amount.number_of_pages - 1
+        };
+
+        let page = req.page.unwrap_or(0).clamp(0, max_page);
+
+        // Get items from page
+        let items = paginate
+            .fetch_page(page)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(MangasReply {
+            pagination: Some(PaginateReply {
+                page,
+                per_page,
+                max_page,
+                total: amount.number_of_items,
+            }),
+            items: items.into_iter().map(|manga| manga.into()).collect(),
+        }))
+    }
+}
+
+crate::export_service!(CollectionServer, CollectionController, auth = UserPermissions::USER);
@@ -1,31 +1,46 @@
+use std::collections::HashSet;
 use std::pin::Pin;
 use std::time::Duration;
 
 use chrono::{NaiveDateTime, Utc};
 use futures::Stream;
-use manga_parser::scraper::MangaScraper;
 use manga_parser::Url;
-use migration::{Expr, IntoCondition, JoinType, OnConflict};
+use migration::{Alias, Expr, IntoCondition, JoinType, OnConflict};
 use sea_orm::ActiveValue::{NotSet, Set};
 use sea_orm::{
     ActiveModelTrait, ColumnTrait, DatabaseConnection, DeriveColumn, EntityTrait, EnumIter, PaginatorTrait,
-    QueryFilter, QueryOrder, QuerySelect, QueryTrait, RelationTrait, Select,
+    QueryFilter, QueryOrder, QuerySelect, QueryTrait, RelationDef, RelationTrait, Select,
 };
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_stream::StreamExt;
 use tonic::{Request, Response, Status};
 
+use super::chapter::download_pages;
+use crate::interceptor::auth::{verify_session, Token, UserPermissions};
 use crate::proto::manga_server::{Manga, MangaServer};
-use crate::proto::{Id, MangaReply, MangaRequest, MangasReply, MangasRequest, PaginateReply, PaginateSearchQuery};
+use crate::proto::{
+    self, Id, MangaDownloadChunk, MangaDownloadRequest, MangaHealthReply, MangaReply, MangaRequest, MangaWatchReply,
+    MangaWatchRequest, MangasReply, MangasRequest, PaginateReply, PaginateSearchQuery,
+};
 use crate::util::auth::Authorize;
+use crate::util::bus::{self, MangaUpdate};
+use crate::util::chapter_diff::Scraped;
 use crate::util::db::DatabaseRequest;
 use crate::util::scrape_error_proto::StatusWrapper;
 use crate::util::search::manga::lucene_filter;
+use crate::util::{cbz, chapter_diff, chapter_image, chapter_read, scrape_failure};
 use crate::{data, util, MANGA_PARSER};
 
 type ResponseStream = Pin<Box<dyn Stream<Item = Result<MangaReply, Status>> + Send>>;
 
+/// Chapters that can be downloaded with one request
+const MAX_DOWNLOAD_CHAPTERS: usize = 50;
+const DOWNLOAD_CHUNK_SIZE: usize = 64 * 1024;
+/// How often a watcher checks that its session is still valid when there are no updates
+const WATCH_SESSION_INTERVAL: Duration = Duration::from_secs(60);
+
 #[derive(Copy, Clone, Debug, EnumIter, DeriveColumn)]
 enum MangaOnlyUrlAndId {
     Id,
@@ -35,18 +50,38 @@ enum MangaOnlyUrlAndId {
 pub const NEXT_UPDATE_QUERY: &str =
     "(MAX(chapter.posted) + (MAX(chapter.posted) - MIN(chapter.posted)) / NULLIF(COUNT(*) - 1, 0))";
 
+/// Amount of chapters the logged in user has read, derived from the read chapters so it survives chapter rewrites
+pub const PROGRESS_QUERY: &str = r#"
+CASE WHEN reading.user_id IS NULL THEN NULL ELSE (
+    SELECT COUNT(*)::int FROM chapter_read
+    JOIN chapter read_chapter ON read_chapter.id = chapter_read.chapter_id
+    WHERE read_chapter.manga_id = manga.id AND chapter_read.user_id = reading.user_id AND read_chapter.deleted_at IS NULL
+) END"#;
+
+/// Relation from a manga to its chapters that are not deleted
+pub fn active_chapters() -> RelationDef {
+    entity::manga::Relation::Chapter.def().on_condition(|_left, right| {
+        Expr::col((right, entity::chapter::Column::DeletedAt))
+            .is_null()
+            .into_condition()
+    })
+}
+
 /// Get a "full" manga by it's ID
 #[rustfmt::skip]
 pub async fn get_manga_by_id(db: &DatabaseConnection, logged_in: Option<&entity::user::Model>, manga_id: i32) -> Result<MangaReply, Status> {
     use entity::chapter::Column as ChapterColumn;
 
     let manga = entity::manga::Entity::find_by_id(manga_id)
-        .left_join(entity::chapter::Entity)
+        .join(JoinType::LeftJoin, active_chapters())
         .column_as(ChapterColumn::Id.count(), "count_chapters")
         .column_as(ChapterColumn::Posted.max(), "last")
         .column_as(Expr::cust(NEXT_UPDATE_QUERY), "next")
         .group_by(entity::manga::Column::Id)
         .column_as(Expr::cust("null"), "progress")
+        .column_as(Expr::cust("null"), "reading_status")
+        .column_as(Expr::cust("null"), "muted")
+        .column_as(Expr::cust("null"), "notify_caught_up")
         .apply_if(logged_in, |query, logged_in| {
             let user_id = logged_in.id;
             query
@@ -60,7 +95,13 @@ pub async fn get_manga_by_id(db: &DatabaseConnection, logged_in: Option<&entity:
                         },
                     ),
                 )
-                .column_as(entity::reading::Column::Progress, "progress")
+                .column_as(Expr::cust(PROGRESS_QUERY), "progress")
+                .column_as(
+                    Expr::col((entity::reading::Entity, entity::reading::Column::Status)).cast_as(Alias::new("text")),
+                    "reading_status",
+                )
+                .column_as(entity::reading::Column::Muted, "muted")
+                .column_as(entity::reading::Column::NotifyCaughtUp, "notify_caught_up")
                 .group_by(entity::reading::Column::UserId)
                 .group_by(entity::reading::Column::MangaId)
         })
@@ -84,7 +125,17 @@ pub async fn save_manga(
 
     // TODO: backtick and probably other special characters
     // TODO: should be replaced with normal characters
-    let manga: manga_parser::model::Manga = MANGA_PARSER.manga(&url).await.map_err(StatusWrapper::from)?;
+    let manga: manga_parser::model::Manga = match MANGA_PARSER.manga(&url).await {
+        Ok(manga) => manga,
+        Err(e) => {
+            let error = proto::ScrapeError::from(e);
+            // Only saved manga have a failure history
+            if let Some(manga_id) = id {
+                scrape_failure::record(db, manga_id, &error).await?;
+            }
+            return Err(StatusWrapper::from(error).into());
+        }
+    };
 
     let saved = entity::manga::ActiveModel {
         id: id.map_or(NotSet, Set),
@@ -108,58 +159,131 @@ pub async fn save_manga(
     if manga.chapters.is_empty() {
         error!("No chapters found for {} [{}]", manga_id, manga.url.to_string());
     } else {
-        if id.is_some() {
-            let count_chapters = entity::chapter::Entity::find()
-                .filter(entity::chapter::Column::MangaId.eq(manga_id))
-                .count(db)
+        // Compare with all saved chapters, so removed chapters can be found again
+        let saved_chapters = entity::chapter::Entity::find()
+            .filter(entity::chapter::Column::MangaId.eq(manga_id))
+            .order_by_asc(entity::chapter::Column::Position)
+            .order_by_asc(entity::chapter::Column::Id)
+            .all(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let scraped: Vec<Scraped> = manga.chapters.iter().rev().map(Scraped::from).collect();
+        let diff = chapter_diff::diff(manga_id, &saved_chapters, &scraped);
+        let updated = diff.updated.len();
+        let deleted = diff.deleted.len();
+
+        for chapter in diff.updated {
+            chapter.update(db).await.map_err(|e| Status::internal(e.to_string()))?;
+        }
+        // A chapter with a new URL may link to other images now
+        chapter_image::forget(db, diff.relinked).await?;
+
+        // Chapters are soft-deleted, so offsets and read chapters are kept if they show up again
+        if !diff.deleted.is_empty() {
+            entity::chapter::Entity::update_many()
+                .col_expr(entity::chapter::Column::DeletedAt, Expr::current_timestamp().into())
+                .filter(entity::chapter::Column::Id.is_in(diff.deleted))
+                .exec(db)
                 .await
                 .map_err(|e| Status::internal(e.to_string()))?;
-            if (manga.chapters.len() as u64) < count_chapters {
-                // If there are suddenly less chapters than we have in our database, we reset our chapters
-                // Remove old chapters
-                let res = entity::chapter::Entity::delete_many()
-                    .filter(entity::chapter::Column::MangaId.eq(manga_id))
-                    .exec(db)
-                    .await
-                    .map_err(|e| Status::internal(e.to_string()))?;
-                info!("Cleared {} chapter(s)", res.rows_affected);
-            }
         }
 
-        // Add new chapters
-        let mut chapters = vec![];
-        for chapter in manga.chapters.iter().rev() {
-            chapters.push(entity::chapter::ActiveModel {
-                manga_id: Set(manga_id),
-                number: Set(chapter.number),
-                url: Set(chapter.url.to_string()),
-                title: Set(chapter.title.clone()),
-                posted: Set(chapter.date.map(|date| date.into())),
-                ..Default::default()
-            });
-        }
-        info!("Inserting {} chapter(s)", chapters.len());
+        // New chapters get higher IDs than every saved chapter
+        let last_chapter_id = saved_chapters.iter().map(|chapter| chapter.id).max();
+
         // Insert all in batch
-        let res = entity::chapter::Entity::insert_many(chapters)
-            .on_conflict(OnConflict::column(entity::chapter::Column::Url).do_nothing().to_owned())
-            .exec_without_returning(db)
-            .await
-            .map_err(|e| Status::internal(e.to_string()))?;
+        let inserted = if diff.inserted.is_empty() {
+            0
+        } else {
+            entity::chapter::Entity::insert_many(diff.inserted)
+                .on_conflict(OnConflict::column(entity::chapter::Column::Url).do_nothing().to_owned())
+                .exec_without_returning(db)
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?
+        };
+
+        info!(
+            "Chapters of {}: {} kept, {} updated, {} deleted, {} inserted",
+            manga_id, diff.kept, updated, deleted, inserted
+        );
+
+        if deleted > 0 || diff.restored > 0 {
+            chapter_read::sync_progress(db, manga_id).await?;
+        }
 
-        info!("Inserted {} unique chapter(s)", res);
+        if inserted > 0 {
+            let chapters = entity::chapter::Entity::find()
+                .filter(entity::chapter::Column::MangaId.eq(manga_id))
+                .filter(entity::chapter::Column::DeletedAt.is_null())
+                .apply_if(last_chapter_id, |query, last_chapter_id| {
+                    query.filter(entity::chapter::Column::Id.gt(last_chapter_id))
+                })
+                .order_by_asc(entity::chapter::Column::Position)
+                .all(db)
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?;
+            bus::publish(MangaUpdate { manga_id, chapters });
+        }
     }
 
     get_manga_by_id(db, logged_in, manga_id).await
 }
 
+/// An update as the user sees it, only for manga the user reads
+async fn watch_reply(
+    db: &DatabaseConnection,
+    logged_in: &entity::user::Model,
+    update: MangaUpdate,
+) -> Result<Option<MangaWatchReply>, Status> {
+    let reading = entity::reading::Entity::find_by_id((logged_in.id, update.manga_id))
+        .one(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+    if reading.is_none() {
+        return Ok(None);
+    }
+
+    let manga = get_manga_by_id(db, Some(logged_in), update.manga_id).await?;
+
+    let chapters = update
+        .chapters
+        .into_iter()
+        .map(|chapter| {
+            // Positions start at 0, indexes at 1
+            let index = chapter.position as i64 + 1;
+            data::chapter::Full {
+                id: chapter.id,
+                manga_id: chapter.manga_id,
+                url: chapter.url,
+                title: chapter.title,
+                number: chapter.number,
+                posted: chapter.posted,
+                created_at: chapter.created_at,
+                updated_at: chapter.updated_at,
+                offset: None,
+                page: None,
+            }
+            .into_chapter_reply(index)
+        })
+        .collect();
+
+    Ok(Some(MangaWatchReply {
+        manga: Some(manga),
+        chapters,
+    }))
+}
+
 pub fn index_manga(logged_in: Option<entity::user::Model>) -> Select<entity::manga::Entity> {
     entity::manga::Entity::find()
-        .left_join(entity::chapter::Entity)
+        .join(JoinType::LeftJoin, active_chapters())
         .column_as(entity::chapter::Column::Id.count(), "count_chapters")
         .column_as(entity::chapter::Column::Posted.max(), "last")
         .column_as(Expr::cust(NEXT_UPDATE_QUERY), "next")
         .group_by(entity::manga::Column::Id)
         .column_as(Expr::cust("null"), "progress")
+        .column_as(Expr::cust("null"), "reading_status")
+        .column_as(Expr::cust("null"), "muted")
+        .column_as(Expr::cust("null"), "notify_caught_up")
         .apply_if(logged_in, |query, logged_in| {
             let user_id = logged_in.id;
             query
@@ -174,7 +298,13 @@ pub fn index_manga(logged_in: Option<entity::user::Model>) -> Select<entity::man
                                 .into_condition()
                         }),
                 )
-                .column_as(entity::reading::Column::Progress, "progress")
+                .column_as(Expr::cust(PROGRESS_QUERY), "progress")
+                .column_as(
+                    Expr::col((entity::reading::Entity, entity::reading::Column::Status)).cast_as(Alias::new("text")),
+                    "reading_status",
+                )
+                .column_as(entity::reading::Column::Muted, "muted")
+                .column_as(entity::reading::Column::NotifyCaughtUp, "notify_caught_up")
                 .group_by(entity::reading::Column::MangaId)
                 .group_by(entity::reading::Column::UserId)
         })
@@ -186,6 +316,8 @@ pub struct MangaController;
 #[tonic::async_trait]
 impl Manga for MangaController {
     type CreateManyStream = ResponseStream;
+    type DownloadChaptersStream = Pin<Box<dyn Stream<Item = Result<MangaDownloadChunk, Status>> + Send>>;
+    type WatchStream = Pin<Box<dyn Stream<Item = Result<MangaWatchReply, Status>> + Send>>;
 
     /// Create one manga
     async fn create(&self, request: Request<MangaRequest>) -> Result<Response<MangaReply>, Status> {
@@ -225,7 +357,8 @@ impl Manga for MangaController {
             ))?
             .clone();
         let req = request.get_ref();
-        let mut stream = Box::pin(tokio_stream::iter(req.urls.clone()).throttle(Duration::from_millis(200)));
+        // Requests to the same website are paced by `MANGA_PARSER`
+        let mut stream = Box::pin(tokio_stream::iter(req.urls.clone()));
 
         // spawn and channel are required if you want handle "disconnect" functionality
         // the `out_stream` will not be polled after client disconnect
@@ -430,6 +563,167 @@ impl Manga for MangaController {
             items: similar.into_iter().map(|manga| manga.into()).collect(),
         }))
     }
+
+    /// Get the recent scrape failures of a manga
+    async fn health(&self, request: Request<Id>) -> Result<Response<MangaHealthReply>, Status> {
+        let db = request.db()?;
+        let req = request.get_ref();
+
+        let manga = entity::manga::Entity::find_by_id(req.id)
+            .one(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or(Status::not_found("Manga not found"))?;
+
+        Ok(Response::new(scrape_failure::health(db, &manga).await?))
+    }
+
+    /// Stream new chapters of read manga as soon as they are saved
+    ///
+    /// The stream ends with an error once the session is revoked, the user is banned or updates were missed
+    async fn watch(&self, request: Request<MangaWatchRequest>) -> Result<Response<Self::WatchStream>, Status> {
+        let db = request.db()?.clone();
+        let mut logged_in = request.authorize()?.clone();
+        let claims = request
+            .extensions()
+            .get::<Token>()
+            .cloned()
+            .ok_or(Status::unauthenticated("Not logged in"))?;
+        let manga_ids: HashSet<i32> = request.get_ref().manga_ids.iter().copied().collect();
+
+        let mut updates = bus::subscribe();
+        let mut recheck = tokio::time::interval(WATCH_SESSION_INTERVAL);
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            loop {
+                let update = tokio::select! {
+                    // Client disconnected
+                    _ = tx.closed() => return,
+                    _ = recheck.tick() => None,
+                    update = updates.recv() => match update {
+                        Ok(update) => Some(update),
+                        Err(RecvError::Lagged(missed)) => {
+                            warn!("Watcher {} missed {} manga updates", logged_in.id, missed);
+                            let _ = tx
+                                .send(Err(Status::data_loss(format!(
+                                    "Missed {missed} manga updates, fetch the read manga again and watch again"
+                                ))))
+                                .await;
+                            return;
+                        }
+                        Err(RecvError::Closed) => return,
+                    },
+                };
+
+                if update
+                    .as_ref()
+                    .is_some_and(|update| !manga_ids.is_empty() && !manga_ids.contains(&update.manga_id))
+                {
+                    continue;
+                }
+
+                // The session can be revoked or the user banned while watching
+                match verify_session(&db, &claims).await {
+                    Ok((_session, user)) => logged_in = user,
+                    Err(e) => {
+                        let _ = tx.send(Err(e)).await;
+                        return;
+                    }
+                }
+
+                let Some(update) = update else {
+                    continue;
+                };
+                let reply = match watch_reply(&db, &logged_in, update).await {
+                    Ok(Some(reply)) => Ok(reply),
+                    Ok(None) => continue,
+                    Err(e) => Err(e),
+                };
+                if tx.send(reply).await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx)) as Self::WatchStream))
+    }
+
+    /// Download a range of chapters, every chapter as a CBZ archive split in chunks
+    async fn download_chapters(
+        &self,
+        request: Request<MangaDownloadRequest>,
+    ) -> Result<Response<Self::DownloadChaptersStream>, Status> {
+        let db = request.db()?;
+        let req = request.get_ref();
+
+        let manga = entity::manga::Entity::find_by_id(req.manga_id)
+            .one(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or(Status::not_found("Manga not found"))?;
+
+        let chapter_ids = chapter_read::chapter_range(db, manga.id, req.from_chapter_id, req.to_chapter_id).await?;
+        if chapter_ids.len() > MAX_DOWNLOAD_CHAPTERS {
+            return Err(Status::invalid_argument(format!(
+                "At most {MAX_DOWNLOAD_CHAPTERS} chapters can be downloaded at once"
+            )));
+        }
+
+        let chapters = entity::chapter::Entity::find()
+            .filter(entity::chapter::Column::Id.is_in(chapter_ids))
+            .order_by_asc(entity::chapter::Column::Position)
+            .all(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let db = db.clone();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            for chapter in chapters {
+                let pages = match download_pages(&db, &chapter).await {
+                    Ok(pages) => pages,
+                    Err(e) => {
+                        let _ = tx.send(Err(e)).await;
+                        return;
+                    }
+                };
+
+                let archive = cbz::write(&cbz::comic_info(&manga, &chapter, pages.len()), &pages);
+                let file_name = cbz::file_name(&manga, &chapter);
+                let count = archive.len().div_ceil(DOWNLOAD_CHUNK_SIZE);
+
+                for (index, data) in archive.chunks(DOWNLOAD_CHUNK_SIZE).enumerate() {
+                    let chunk = MangaDownloadChunk {
+                        chapter_id: chapter.id,
+                        file_name: file_name.clone(),
+                        data: data.to_vec(),
+                        last: index + 1 == count,
+                    };
+                    if tx.send(Ok(chunk)).await.is_err() {
+                        // Client disconnected
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(Response::new(
+            Box::pin(ReceiverStream::new(rx)) as Self::DownloadChaptersStream
+        ))
+    }
 }
 
-crate::export_service!(MangaServer, MangaController);
+crate::export_service!(
+    MangaServer,
+    MangaController,
+    auth = {
+        "Create" => UserPermissions::USER,
+        "CreateMany" => UserPermissions::USER,
+        "FindOrCreate" => UserPermissions::USER,
+        "DownloadChapters" => UserPermissions::USER,
+        "Watch" => UserPermissions::USER,
+        "Health" => UserPermissions::USER,
+        "Update" => UserPermissions::MOD,
+    },
+    public = ["Get", "Index", "Similar"]
+);
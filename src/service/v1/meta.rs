@@ -1,4 +1,4 @@
-use manga_parser::scraper::MangaScraper;
+use chrono::{NaiveDateTime, Utc};
 use migration::{Expr, IntoCondition, JoinType};
 use sea_orm::{
     ColumnTrait, DatabaseConnection, DeriveColumn, EntityTrait, EnumIter, QueryFilter, QueryOrder, QuerySelect,
@@ -6,9 +6,13 @@ use sea_orm::{
 };
 use tonic::{Request, Response, Status};
 
+use super::manga::active_chapters;
+use crate::data::reading_history::date_from_millis;
+use crate::interceptor::auth::UserPermissions;
 use crate::proto::meta_server::{Meta, MetaServer};
 use crate::proto::{
-    Empty, MetaGenresOption, MetaGenresRequest, MetaHostnamesOption, MetaHostnamesRequest, MetaReply, StatsReply,
+    MetaGenresOption, MetaGenresRequest, MetaHostnamesOption, MetaHostnamesRequest, MetaReply, StatsInterval,
+    StatsPeriod, StatsReply, StatsRequest,
 };
 use crate::util::auth::Authorize;
 use crate::util::db::DatabaseRequest;
@@ -134,9 +138,10 @@ impl Meta for MetaController {
         ))
     }
 
-    async fn stats(&self, req: Request<Empty>) -> Result<Response<StatsReply>, Status> {
+    async fn stats(&self, req: Request<StatsRequest>) -> Result<Response<StatsReply>, Status> {
         let logged_in = req.authorize()?;
         let db = req.db()?;
+        let request = req.get_ref();
 
         let user_id = logged_in.id;
 
@@ -144,7 +149,7 @@ impl Meta for MetaController {
             .filter(entity::reading::Column::UserId.eq(user_id))
             .select_only()
             .column_as(
-                Expr::cust("COUNT(CASE WHEN ((SELECT COUNT(*) FROM chapter WHERE chapter.manga_id = reading.manga_id) = reading.progress) THEN 1 END)"),
+                Expr::cust("COUNT(CASE WHEN ((SELECT COUNT(*) FROM chapter WHERE chapter.manga_id = reading.manga_id AND chapter.deleted_at IS NULL) = reading.progress) THEN 1 END)"),
                 "count_reading",
             )
             .into_tuple()
@@ -156,7 +161,7 @@ impl Meta for MetaController {
         let stats: (i64, i64, i64) = entity::reading::Entity::find()
             .filter(entity::reading::Column::UserId.eq(user_id))
             .left_join(entity::manga::Entity)
-            .join(JoinType::LeftJoin, entity::manga::Relation::Chapter.def())
+            .join(JoinType::LeftJoin, active_chapters())
             .select_only()
             .column_as(Expr::cust("COUNT(DISTINCT reading.manga_id)"), StatsCount::TotalReading)
             .column_as(Expr::cust("COUNT(DISTINCT chapter.id)"), StatsCount::TotalChapters)
@@ -167,13 +172,59 @@ impl Meta for MetaController {
             .map_err(|e| Status::internal(e.to_string()))?
             .ok_or(Status::unknown("How?"))?;
 
+        // Chapters read per day or week, periods without any are left out
+        let (unit, default_range) = match request.interval() {
+            StatsInterval::Day => ("day", chrono::Duration::days(30)),
+            StatsInterval::Week => ("week", chrono::Duration::weeks(12)),
+        };
+        let to = request
+            .to
+            .map(date_from_millis)
+            .transpose()?
+            .unwrap_or(Utc::now().naive_utc());
+        let from = request
+            .from
+            .map(date_from_millis)
+            .transpose()?
+            .unwrap_or(to - default_range);
+        let period_start = format!("DATE_TRUNC('{unit}', reading_history.created_at)");
+
+        let chapters_read: Vec<(NaiveDateTime, i64)> = entity::reading_history::Entity::find()
+            .filter(entity::reading_history::Column::UserId.eq(user_id))
+            .filter(entity::reading_history::Column::Read.eq(true))
+            .filter(entity::reading_history::Column::CreatedAt.gte(from))
+            .filter(entity::reading_history::Column::CreatedAt.lt(to))
+            .select_only()
+            .column_as(Expr::cust(period_start.clone()), "start")
+            .column_as(entity::reading_history::Column::Id.count(), "count")
+            .group_by(Expr::cust(period_start.clone()))
+            .order_by_asc(Expr::cust(period_start))
+            .into_tuple()
+            .all(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
         Ok(Response::new(StatsReply {
             count_total_reading: stats.0,
             count_total_chapters: stats.1,
             count_reading,
             count_chapters: stats.2,
+            chapters_read: chapters_read
+                .into_iter()
+                .map(|(start, count)| StatsPeriod {
+                    start: start.and_utc().timestamp_millis(),
+                    count,
+                })
+                .collect(),
         }))
     }
 }
 
-crate::export_service!(MetaServer, MetaController);
+crate::export_service!(
+    MetaServer,
+    MetaController,
+    auth = {
+        "Stats" => UserPermissions::USER,
+    },
+    public = ["Hostnames", "Genres"]
+);
@@ -1,7 +1,10 @@
+pub mod admin;
 pub mod chapter;
+pub mod collection;
 pub mod friend;
 pub mod manga;
 pub mod meta;
+pub mod notification;
 pub mod reading;
 pub mod search;
 pub mod user;
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+
+use chrono::NaiveTime;
+use chrono_tz::Tz;
+use migration::{Expr, OnConflict};
+use sea_orm::ActiveValue::Set;
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait,
+};
+use tonic::{Request, Response, Status};
+
+use crate::data;
+use crate::interceptor::auth::UserPermissions;
+use crate::proto::notification_server::{Notification, NotificationServer};
+use crate::proto::{
+    self, DeviceReply, DevicesReply, Empty, NotificationIndexRequest, NotificationMarkReadRequest,
+    NotificationSettingsReply, NotificationSettingsRequest, NotificationUnreadReply, NotificationsReply, PaginateReply,
+};
+use crate::util::auth::Authorize;
+use crate::util::db::DatabaseRequest;
+use crate::util::{notifier, verify};
+
+/// An empty string removes an optional setting
+async fn optional_url(value: &str) -> Result<Option<String>, Status> {
+    if value.trim().is_empty() {
+        Ok(None)
+    } else {
+        verify::public_http_url(value).await.map(Some)
+    }
+}
+
+/// Amount of unread notifications of a user
+async fn unread_count(db: &DatabaseConnection, user_id: i32) -> Result<i64, Status> {
+    let unread = entity::notification::Entity::find()
+        .filter(entity::notification::Column::UserId.eq(user_id))
+        .filter(entity::notification::Column::Read.eq(false))
+        .count(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(unread as i64)
+}
+
+/// A time of day as "HH:MM", an empty string removes it
+fn optional_time(value: &str) -> Result<Option<NaiveTime>, Status> {
+    if value.trim().is_empty() {
+        Ok(None)
+    } else {
+        NaiveTime::parse_from_str(value.trim(), "%H:%M")
+            .map(Some)
+            .map_err(|_| Status::invalid_argument("Time should be formatted as HH:MM"))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct NotificationController;
+
+#[tonic::async_trait]
+impl Notification for NotificationController {
+    /// Get the notification settings of the logged in user
+    async fn settings(&self, request: Request<Empty>) -> Result<Response<NotificationSettingsReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+
+        let settings = notifier::settings(db, logged_in.id).await?;
+
+        Ok(Response::new(settings.into()))
+    }
+
+    /// Choose the channels notifications are sent through and when not to send them
+    async fn update_settings(
+        &self,
+        request: Request<NotificationSettingsRequest>,
+    ) -> Result<Response<NotificationSettingsReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        let mut settings = notifier::settings(db, logged_in.id).await?;
+
+        if let Some(fcm) = req.fcm {
+            settings.fcm = fcm;
+        }
+        if let Some(email) = req.email {
+            if email && !logged_in.email_verified {
+                return Err(Status::failed_precondition(
+                    "Verify your email before enabling email notifications",
+                ));
+            }
+            settings.email = email;
+        }
+        if let Some(webhook_url) = &req.webhook_url {
+            settings.webhook_url = optional_url(webhook_url).await?;
+        }
+        if let Some(push_service) = req.push_service {
+            settings.push_service = proto::PushService::try_from(push_service)
+                .map_err(|_| Status::invalid_argument("Unknown push service"))?
+                .into();
+        }
+        if let Some(push_url) = &req.push_url {
+            settings.push_url = optional_url(push_url).await?;
+        }
+        if let Some(push_token) = &req.push_token {
+            let push_token = push_token.trim();
+            settings.push_token = (!push_token.is_empty()).then(|| push_token.to_string());
+        }
+        if let Some(quiet_start) = &req.quiet_start {
+            settings.quiet_start = optional_time(quiet_start)?;
+        }
+        if let Some(quiet_end) = &req.quiet_end {
+            settings.quiet_end = optional_time(quiet_end)?;
+        }
+        if let Some(timezone) = &req.timezone {
+            let timezone: Tz = timezone
+                .trim()
+                .parse()
+                .map_err(|_| Status::invalid_argument("Unknown timezone"))?;
+            settings.timezone = timezone.name().to_string();
+        }
+
+        let saved = entity::notification_setting::Entity::insert(entity::notification_setting::ActiveModel {
+            user_id: Set(logged_in.id),
+            fcm: Set(settings.fcm),
+            email: Set(settings.email),
+            webhook_url: Set(settings.webhook_url),
+            push_service: Set(settings.push_service),
+            push_url: Set(settings.push_url),
+            push_token: Set(settings.push_token),
+            quiet_start: Set(settings.quiet_start),
+            quiet_end: Set(settings.quiet_end),
+            timezone: Set(settings.timezone),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::column(entity::notification_setting::Column::UserId)
+                .update_columns([
+                    entity::notification_setting::Column::Fcm,
+                    entity::notification_setting::Column::Email,
+                    entity::notification_setting::Column::WebhookUrl,
+                    entity::notification_setting::Column::PushService,
+                    entity::notification_setting::Column::PushUrl,
+                    entity::notification_setting::Column::PushToken,
+                    entity::notification_setting::Column::QuietStart,
+                    entity::notification_setting::Column::QuietEnd,
+                    entity::notification_setting::Column::Timezone,
+                ])
+                .to_owned(),
+        )
+        .exec_with_returning(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(saved.into()))
+    }
+
+    /// Inbox of the logged in user, newest first
+    async fn index(&self, request: Request<NotificationIndexRequest>) -> Result<Response<NotificationsReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+        let paginate_query = req.paginate_query.unwrap_or_default();
+        let per_page = paginate_query.per_page.unwrap_or(10).clamp(1, 50);
+
+        let paginate = entity::notification::Entity::find()
+            .filter(entity::notification::Column::UserId.eq(logged_in.id))
+            .apply_if(req.unread_only.then_some(false), |query, read| {
+                query.filter(entity::notification::Column::Read.eq(read))
+            })
+            .inner_join(entity::manga::Entity)
+            .column_as(entity::manga::Column::Title, "manga_title")
+            .order_by_desc(entity::notification::Column::Id)
+            .into_model::<data::notification::Full>()
+            .paginate(db, per_page);
+
+        // Get max page and total items
+        let amount = paginate
+            .num_items_and_pages()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let max_page = if amount.number_of_pages == 0 {
+            0
+        } else {
+            amount.number_of_pages - 1
+        };
+
+        let page = paginate_query.page.unwrap_or(0).clamp(0, max_page);
+
+        // Get items from page
+        let items = paginate
+            .fetch_page(page)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(NotificationsReply {
+            pagination: Some(PaginateReply {
+                page,
+                per_page,
+                max_page,
+                total: amount.number_of_items,
+            }),
+            items: items.into_iter().map(|notification| notification.into()).collect(),
+            unread: unread_count(db, logged_in.id).await?,
+        }))
+    }
+
+    /// Mark notifications of the logged in user as read
+    async fn mark_read(
+        &self,
+        request: Request<NotificationMarkReadRequest>,
+    ) -> Result<Response<NotificationUnreadReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        if !req.all && req.ids.is_empty() {
+            return Err(Status::invalid_argument("No notifications to mark as read"));
+        }
+
+        entity::notification::Entity::update_many()
+            .col_expr(entity::notification::Column::Read, Expr::value(true))
+            .filter(entity::notification::Column::UserId.eq(logged_in.id))
+            .filter(entity::notification::Column::Read.eq(false))
+            .apply_if((!req.all).then(|| req.ids.clone()), |query, ids| {
+                query.filter(entity::notification::Column::Id.is_in(ids))
+            })
+            .exec(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(NotificationUnreadReply {
+            unread: unread_count(db, logged_in.id).await?,
+        }))
+    }
+
+    /// Amount of unread notifications of the logged in user
+    async fn unread_count(&self, request: Request<Empty>) -> Result<Response<NotificationUnreadReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+
+        Ok(Response::new(NotificationUnreadReply {
+            unread: unread_count(db, logged_in.id).await?,
+        }))
+    }
+
+    /// Delivery stats of the device tokens of the logged in user
+    async fn devices(&self, request: Request<Empty>) -> Result<Response<DevicesReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+
+        let mut stats: HashMap<String, entity::device_token_stat::Model> = entity::device_token_stat::Entity::find()
+            .filter(entity::device_token_stat::Column::UserId.eq(logged_in.id))
+            .all(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .into_iter()
+            .map(|stat| (stat.token.clone(), stat))
+            .collect();
+
+        // Tokens that never received a notification have no stats yet
+        let items = logged_in
+            .device_ids
+            .iter()
+            .map(|token| {
+                stats.remove(token).map_or_else(
+                    || DeviceReply {
+                        token: token.clone(),
+                        ..Default::default()
+                    },
+                    DeviceReply::from,
+                )
+            })
+            .collect();
+
+        Ok(Response::new(DevicesReply { items }))
+    }
+}
+
+crate::export_service!(NotificationServer, NotificationController, auth = UserPermissions::USER);
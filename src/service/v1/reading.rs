@@ -1,19 +1,59 @@
-use sea_orm::ActiveValue::{self, Set};
-use sea_orm::{ActiveModelTrait, EntityTrait, IntoActiveModel};
+use std::pin::Pin;
+use std::time::Duration;
+
+use futures::Stream;
+use migration::Query;
+use sea_orm::ActiveValue::{self, NotSet, Set};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect,
+    QueryTrait,
+};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::StreamExt;
 use tonic::{Request, Response, Status};
 
 use super::manga::get_manga_by_id;
+use crate::data;
+use crate::data::reading::status_from_i32;
+use crate::data::reading_history::date_from_millis;
 use crate::interceptor::auth::UserPermissions;
 use crate::proto::reading_server::{Reading, ReadingServer};
-use crate::proto::{Empty, Id, MangaReply, ReadingPatchRequest, ReadingPostRequest, UpdateChapterOffsetRequest};
+use crate::proto::{
+    Empty, ExportFormat, Id, ImportFormat, MangaReply, PaginateReply, ReadingExportReply, ReadingExportRequest,
+    ReadingHistoriesReply, ReadingHistoryRequest, ReadingImportReply, ReadingImportRequest, ReadingMarkRequest,
+    ReadingPatchRequest, ReadingPostRequest, UpdateChapterOffsetRequest,
+};
 use crate::util::auth::Authorize;
 use crate::util::db::DatabaseRequest;
+use crate::util::{chapter_read, export, history, import};
+
+/// Mark the chapters from one chapter up to and including another as read or unread
+async fn mark_range(request: Request<ReadingMarkRequest>, read: bool) -> Result<Response<MangaReply>, Status> {
+    let db = request.db()?;
+    let logged_in = request.authorize()?;
+    let req = request.get_ref();
+
+    entity::reading::Entity::find_by_id((logged_in.id, req.manga_id))
+        .one(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .ok_or(Status::not_found("Reading not found"))?;
+
+    let chapter_ids = chapter_read::chapter_range(db, req.manga_id, req.from_chapter_id, req.to_chapter_id).await?;
+    let (changed, progress) = chapter_read::mark(db, logged_in.id, req.manga_id, &chapter_ids, read).await?;
+    history::record(db, logged_in.id, req.manga_id, &changed, read, progress).await?;
+
+    Ok(Response::new(get_manga_by_id(db, Some(logged_in), req.manga_id).await?))
+}
 
 #[derive(Debug, Default)]
 pub struct ReadingController;
 
 #[tonic::async_trait]
 impl Reading for ReadingController {
+    type ImportStream = Pin<Box<dyn Stream<Item = Result<ReadingImportReply, Status>> + Send>>;
+
     /// Edit reading progress
     async fn update(&self, request: Request<ReadingPatchRequest>) -> Result<Response<MangaReply>, Status> {
         let db = request.db()?;
@@ -27,14 +67,43 @@ impl Reading for ReadingController {
             .ok_or(Status::not_found("Reading not found"))?
             .into_active_model();
 
-        reading.progress = Set(req.progress);
+        if let Some(status) = req.status {
+            reading.status = Set(status_from_i32(status)?);
+        }
+        if let Some(muted) = req.muted {
+            reading.muted = Set(muted);
+        }
+        if let Some(notify_caught_up) = req.notify_caught_up {
+            reading.notify_caught_up = Set(notify_caught_up);
+        }
         let reading = reading.update(db).await.map_err(|e| Status::internal(e.to_string()))?;
 
+        // Progress is the amount of chapters read, so the first chapters are read and the others are not
+        if let Some(progress) = req.progress {
+            let chapter_ids = chapter_read::chapter_ids(db, reading.manga_id).await?;
+            let (read, unread) = chapter_ids.split_at((progress.max(0) as usize).min(chapter_ids.len()));
+            for (chapter_ids, read) in [(read, true), (unread, false)] {
+                let (changed, progress) =
+                    chapter_read::mark(db, logged_in.id, reading.manga_id, chapter_ids, read).await?;
+                history::record(db, logged_in.id, reading.manga_id, &changed, read, progress).await?;
+            }
+        }
+
         Ok(Response::new(
             get_manga_by_id(db, Some(logged_in), reading.manga_id).await?,
         ))
     }
 
+    /// Mark a range of chapters as read
+    async fn mark_read(&self, request: Request<ReadingMarkRequest>) -> Result<Response<MangaReply>, Status> {
+        mark_range(request, true).await
+    }
+
+    /// Mark a range of chapters as unread
+    async fn mark_unread(&self, request: Request<ReadingMarkRequest>) -> Result<Response<MangaReply>, Status> {
+        mark_range(request, false).await
+    }
+
     /// Add a new manga to reading
     async fn create(&self, request: Request<ReadingPostRequest>) -> Result<Response<MangaReply>, Status> {
         let db = request.db()?;
@@ -44,6 +113,7 @@ impl Reading for ReadingController {
         let saved = entity::reading::ActiveModel {
             manga_id: Set(req.manga_id),
             user_id: Set(logged_in.id),
+            status: req.status.map(status_from_i32).transpose()?.map_or(NotSet, Set),
             ..Default::default()
         }
         .insert(db)
@@ -69,10 +139,26 @@ impl Reading for ReadingController {
 
         // Check if deleted
         if reading.rows_affected == 0 {
-            Err(Status::not_found("Reading not found"))
-        } else {
-            Ok(Response::new(Empty::default()))
+            return Err(Status::not_found("Reading not found"));
         }
+
+        // Manga that are not read anymore are removed from the collections of the user
+        entity::collection_manga::Entity::delete_many()
+            .filter(entity::collection_manga::Column::MangaId.eq(req.id))
+            .filter(
+                entity::collection_manga::Column::CollectionId.in_subquery(
+                    Query::select()
+                        .column(entity::collection::Column::Id)
+                        .from(entity::collection::Entity)
+                        .and_where(entity::collection::Column::UserId.eq(logged_in.id))
+                        .to_owned(),
+                ),
+            )
+            .exec(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(Empty::default()))
     }
 
     /// Update the initial scroll offset of a chapter
@@ -112,6 +198,111 @@ impl Reading for ReadingController {
 
         Ok(Response::new(Empty::default()))
     }
+
+    /// Import a reading list exported from another site or app
+    ///
+    /// Streams the result of every entry, including the ones that could not be matched
+    async fn import(&self, request: Request<ReadingImportRequest>) -> Result<Response<Self::ImportStream>, Status> {
+        let db = request.db()?.clone();
+        let logged_in = request.authorize()?.clone();
+        let req = request.get_ref();
+
+        let format =
+            ImportFormat::try_from(req.format).map_err(|_| Status::invalid_argument("Unknown import format"))?;
+        let entries = import::parse(format, &req.data)?;
+
+        info!("Importing {} entries for user '{}'", entries.len(), logged_in.username);
+
+        let mut stream = Box::pin(tokio_stream::iter(entries).throttle(Duration::from_millis(200)));
+
+        let (tx, rx) = mpsc::channel(128);
+        tokio::spawn(async move {
+            while let Some(entry) = stream.next().await {
+                let reply = import::import_entry(&db, &logged_in, entry).await;
+
+                if tx.send(Ok(reply)).await.is_err() {
+                    // Client disconnected
+                    break;
+                }
+            }
+        });
+
+        let output_stream = ReceiverStream::new(rx);
+        Ok(Response::new(Box::pin(output_stream) as Self::ImportStream))
+    }
+
+    /// Export the reading list to a file that can be imported elsewhere
+    async fn export(&self, request: Request<ReadingExportRequest>) -> Result<Response<ReadingExportReply>, Status> {
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+
+        let format =
+            ExportFormat::try_from(req.format).map_err(|_| Status::invalid_argument("Unknown export format"))?;
+
+        Ok(Response::new(export::export(db, logged_in, format).await?))
+    }
+
+    /// Paginate the chapters read and unread by the user, newest first
+    async fn history(
+        &self,
+        request: Request<ReadingHistoryRequest>,
+    ) -> Result<Response<ReadingHistoriesReply>, Status> {
+        use entity::reading_history::Column;
+
+        let db = request.db()?;
+        let logged_in = request.authorize()?;
+        let req = request.get_ref();
+        let from = req.from.map(date_from_millis).transpose()?;
+        let to = req.to.map(date_from_millis).transpose()?;
+        let manga_id = req.manga_id;
+        let req = req.paginate_query.unwrap_or_default();
+        let per_page = req.per_page.unwrap_or(10).clamp(1, 50);
+
+        let paginate = entity::reading_history::Entity::find()
+            .filter(Column::UserId.eq(logged_in.id))
+            .apply_if(from, |query, from| query.filter(Column::CreatedAt.gte(from)))
+            .apply_if(to, |query, to| query.filter(Column::CreatedAt.lt(to)))
+            .apply_if(manga_id, |query, manga_id| query.filter(Column::MangaId.eq(manga_id)))
+            .inner_join(entity::manga::Entity)
+            .left_join(entity::chapter::Entity)
+            .column_as(entity::manga::Column::Title, "manga_title")
+            .column_as(entity::chapter::Column::Title, "chapter_title")
+            .column_as(entity::chapter::Column::Number, "chapter_number")
+            .order_by_desc(Column::Id)
+            .into_model::<data::reading_history::Full>()
+            .paginate(db, per_page);
+
+        // Get max page and total items
+        let amount = paginate
+            .num_items_and_pages()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let max_page = if amount.number_of_pages == 0 {
+            0
+        } else {
+            amount.number_of_pages - 1
+        };
+
+        let page = req.page.unwrap_or(0).clamp(0, max_page);
+
+        // Get items from page
+        let items = paginate
+            .fetch_page(page)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(ReadingHistoriesReply {
+            pagination: Some(PaginateReply {
+                page,
+                per_page,
+                max_page,
+                total: amount.number_of_items,
+            }),
+            items: items.into_iter().map(|entry| entry.into()).collect(),
+        }))
+    }
 }
 
 crate::export_service!(ReadingServer, ReadingController, auth = UserPermissions::USER);
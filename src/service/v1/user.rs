@@ -1,22 +1,37 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+
+use chrono::{NaiveDateTime, Utc};
+use futures::Stream;
 use migration::{Alias, Expr, JoinType};
 use sea_orm::{
-    ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, PaginatorTrait,
-    QueryFilter, QuerySelect, RelationTrait,
+    ActiveEnum, ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, RelationTrait,
 };
+use serde_json::json;
 use tonic::{Request, Response, Status};
 
 use crate::data;
-use crate::interceptor::auth::sign;
+use crate::interceptor::auth::UserPermissions;
 use crate::proto::user_request::Identifier;
 use crate::proto::user_server::{User, UserServer};
 use crate::proto::{
-    DeviceTokenRequest, Empty, Id, PaginateQuery, PaginateReply, UserFullReply, UserRegisterRequest, UserReply,
-    UserRequest, UserTokenReply, UserUpdateRequest, UsersReply,
+    DeviceTokenRequest, Empty, Id, PaginateQuery, PaginateReply, PasswordResetRequest, RefreshTokenRequest,
+    ResetPasswordRequest, SessionReply, SessionsReply, UserDeleteRequest, UserExportChunk, UserFullReply,
+    UserRegisterRequest, UserReply, UserRequest, UserTokenReply, UserUpdateRequest, UsersReply, VerifyEmailRequest,
 };
+use crate::util::account_token::{self, Purpose};
 use crate::util::auth::Authorize;
 use crate::util::db::DatabaseRequest;
+use crate::util::mailer::{self, Mail};
+use crate::util::rate_limit::{exhausted, LOGIN_LOCKOUT, USERNAME_LIMITER};
+use crate::util::session::{self, ClientInfo};
+use crate::util::token::{self, TokenPair};
 use crate::util::{argon, verify};
 
+/// Size of the chunks a data export is streamed in
+const EXPORT_CHUNK_SIZE: usize = 64 * 1024;
+
 #[rustfmt::skip]
 pub async fn get_user_by_id(db: &DatabaseConnection, user_id: i32) -> Result<data::user::Full, Status> {
     let following_alias = Alias::new("following");
@@ -37,11 +52,132 @@ pub async fn get_user_by_id(db: &DatabaseConnection, user_id: i32) -> Result<dat
     Ok(user)
 }
 
+/// Create a token reply for the user the tokens belong to
+async fn token_reply(db: &DatabaseConnection, tokens: TokenPair) -> Result<UserTokenReply, Status> {
+    let full_user = get_user_by_id(db, tokens.user_id).await?;
+
+    Ok(UserTokenReply {
+        token: tokens.access_token,
+        refresh_token: tokens.refresh_token,
+        expires_at: tokens.expires_at,
+        user: Some(full_user.into()),
+    })
+}
+
+/// Send a mail with a token to verify the email address
+async fn send_verification_mail(db: &DatabaseConnection, user_id: i32, email: &str) -> Result<(), Status> {
+    let token = account_token::create(db, user_id, Purpose::EmailVerification).await?;
+
+    mailer::send_in_background(Mail {
+        to: email.to_string(),
+        subject: String::from("Verify your email"),
+        body: format!("Use this token to verify your email address:\n\n{token}"),
+    });
+
+    Ok(())
+}
+
+/// Collect everything that is stored about a user
+async fn export_archive(db: &DatabaseConnection, user: &entity::user::Model) -> Result<serde_json::Value, Status> {
+    let readings = entity::reading::Entity::find()
+        .filter(entity::reading::Column::UserId.eq(user.id))
+        .find_also_related(entity::manga::Entity)
+        .all(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    let offsets = entity::chapter_offset::Entity::find()
+        .filter(entity::chapter_offset::Column::UserId.eq(user.id))
+        .find_also_related(entity::chapter::Entity)
+        .all(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    let follows = entity::friend::Entity::find()
+        .filter(
+            entity::friend::Column::UserId
+                .eq(user.id)
+                .or(entity::friend::Column::FriendId.eq(user.id)),
+        )
+        .all(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    // Usernames of everyone this user follows or is followed by
+    let usernames: HashMap<i32, String> = entity::user::Entity::find()
+        .filter(entity::user::Column::Id.is_in(follows.iter().flat_map(|follow| [follow.user_id, follow.friend_id])))
+        .all(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .into_iter()
+        .map(|user| (user.id, user.username))
+        .collect();
+
+    let follow_json = |id: i32, since: NaiveDateTime| {
+        json!({
+            "id": id,
+            "username": usernames.get(&id),
+            "since": since.and_utc().to_rfc3339(),
+        })
+    };
+
+    Ok(json!({
+        "exported_at": Utc::now().to_rfc3339(),
+        "profile": {
+            "id": user.id,
+            "username": user.username,
+            "email": user.email,
+            "email_verified": user.email_verified,
+            "permissions": user.permissions,
+            "preferred_hostnames": user.preferred_hostnames,
+            "device_ids": user.device_ids,
+            "created_at": user.created_at.and_utc().to_rfc3339(),
+            "updated_at": user.updated_at.and_utc().to_rfc3339(),
+        },
+        "reading": readings
+            .into_iter()
+            .map(|(reading, manga)| json!({
+                "manga_id": reading.manga_id,
+                "title": manga.as_ref().map(|manga| &manga.title),
+                "url": manga.as_ref().map(|manga| &manga.url),
+                "progress": reading.progress,
+                "status": reading.status.to_value(),
+                "created_at": reading.created_at.and_utc().to_rfc3339(),
+                "updated_at": reading.updated_at.and_utc().to_rfc3339(),
+            }))
+            .collect::<Vec<_>>(),
+        "chapter_offsets": offsets
+            .into_iter()
+            .map(|(offset, chapter)| json!({
+                "chapter_id": offset.chapter_id,
+                "manga_id": chapter.as_ref().map(|chapter| chapter.manga_id),
+                "number": chapter.as_ref().map(|chapter| chapter.number),
+                "url": chapter.as_ref().map(|chapter| &chapter.url),
+                "offset": offset.offset,
+                "page": offset.page,
+                "updated_at": offset.updated_at.and_utc().to_rfc3339(),
+            }))
+            .collect::<Vec<_>>(),
+        "following": follows
+            .iter()
+            .filter(|follow| follow.user_id == user.id)
+            .map(|follow| follow_json(follow.friend_id, follow.created_at))
+            .collect::<Vec<_>>(),
+        "followers": follows
+            .iter()
+            .filter(|follow| follow.friend_id == user.id)
+            .map(|follow| follow_json(follow.user_id, follow.created_at))
+            .collect::<Vec<_>>(),
+    }))
+}
+
 #[derive(Debug, Default)]
 pub struct UserController;
 
 #[tonic::async_trait]
 impl User for UserController {
+    type ExportStream = Pin<Box<dyn Stream<Item = Result<UserExportChunk, Status>> + Send>>;
+
     /// Get a single user
     async fn get(&self, request: Request<Id>) -> Result<Response<UserFullReply>, Status> {
         let db = request.db()?;
@@ -119,12 +255,11 @@ impl User for UserController {
             Status::internal(e.to_string())
         })?;
 
-        let full_user = get_user_by_id(db, user.id).await?;
-        let token = sign(full_user.id).map_err(|e| Status::aborted(e.to_string()))?;
-        Ok(Response::new(UserTokenReply {
-            token,
-            user: Some(full_user.into()),
-        }))
+        send_verification_mail(db, user.id, &user.email).await?;
+
+        let session = session::create(db, user.id, ClientInfo::from(&request)).await?;
+        let tokens = token::issue(db, user.id, session.id).await?;
+        Ok(Response::new(token_reply(db, tokens).await?))
     }
 
     /// Log in with username/email and password
@@ -135,6 +270,14 @@ impl User for UserController {
 
         let identifier = req.identifier.as_ref().unwrap();
 
+        let key = match identifier {
+            Identifier::Username(username) => username.to_ascii_lowercase(),
+            Identifier::Email(email) => email.to_ascii_lowercase(),
+        };
+        USERNAME_LIMITER
+            .check(key)
+            .map_err(|retry_after| exhausted("Too many login attempts, try again later", retry_after))?;
+
         let filter = match identifier {
             Identifier::Username(username) => entity::user::Column::Username.eq(username.to_ascii_lowercase()),
             Identifier::Email(email) => entity::user::Column::Email.eq(email.to_ascii_lowercase()),
@@ -147,16 +290,85 @@ impl User for UserController {
             .map_err(|e| Status::aborted(e.to_string()))?
             .ok_or(Status::not_found(error))?;
 
-        argon::verify(&user.password_hash, &req.password)?;
+        LOGIN_LOCKOUT
+            .check(&user.id)
+            .map_err(|retry_after| exhausted("Account is locked because of too many failed logins", retry_after))?;
+
+        if let Err(e) = argon::verify(&user.password_hash, &req.password) {
+            LOGIN_LOCKOUT.fail(user.id);
+            return Err(e);
+        }
+        LOGIN_LOCKOUT.reset(&user.id);
+
+        if user.banned_at.is_some() {
+            return Err(Status::permission_denied("This account has been banned"));
+        }
+
+        let session = session::create(db, user.id, ClientInfo::from(&request)).await?;
+        let tokens = token::issue(db, user.id, session.id).await?;
+        Ok(Response::new(token_reply(db, tokens).await?))
+    }
+
+    /// Exchange a refresh token for a new access and refresh token
+    async fn refresh(&self, request: Request<RefreshTokenRequest>) -> Result<Response<UserTokenReply>, Status> {
+        let db = request.db()?;
+        let req = request.get_ref();
+
+        let tokens = token::rotate(db, &req.refresh_token).await?;
+        Ok(Response::new(token_reply(db, tokens).await?))
+    }
+
+    /// End the current session of the logged in user
+    async fn logout(&self, request: Request<Empty>) -> Result<Response<Empty>, Status> {
+        let claims = request.token()?;
+        let db = request.db()?;
+
+        session::revoke(db, claims.id, claims.sid).await?;
+
+        Ok(Response::new(Empty::default()))
+    }
+
+    /// Get all active sessions of the logged in user
+    async fn sessions(&self, request: Request<Empty>) -> Result<Response<SessionsReply>, Status> {
+        let claims = request.token()?;
+        let db = request.db()?;
+
+        let sessions = entity::session::Entity::find()
+            .filter(entity::session::Column::UserId.eq(claims.id))
+            .order_by_desc(entity::session::Column::LastSeenAt)
+            .all(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
 
-        let full_user = get_user_by_id(db, user.id).await?;
-        let token = sign(full_user.id).map_err(|e| Status::aborted(e.to_string()))?;
-        Ok(Response::new(UserTokenReply {
-            token,
-            user: Some(full_user.into()),
+        Ok(Response::new(SessionsReply {
+            items: sessions
+                .into_iter()
+                .map(|session| SessionReply {
+                    id: session.id,
+                    current: session.id == claims.sid,
+                    user_agent: session.user_agent,
+                    remote_addr: session.remote_addr,
+                    last_seen_at: session.last_seen_at.and_utc().timestamp_millis(),
+                    created_at: session.created_at.and_utc().timestamp_millis(),
+                    updated_at: session.updated_at.and_utc().timestamp_millis(),
+                })
+                .collect(),
         }))
     }
 
+    /// End one of the sessions of the logged in user
+    async fn revoke_session(&self, request: Request<Id>) -> Result<Response<Empty>, Status> {
+        let claims = request.token()?;
+        let db = request.db()?;
+        let req = request.get_ref();
+
+        if session::revoke(db, claims.id, req.id).await? {
+            Ok(Response::new(Empty::default()))
+        } else {
+            Err(Status::not_found("Session not found"))
+        }
+    }
+
     /// Get logged in user
     async fn me(&self, request: Request<Empty>) -> Result<Response<UserFullReply>, Status> {
         let logged_in = request.authorize()?;
@@ -176,8 +388,14 @@ impl User for UserController {
         if let Some(username) = &req.username {
             active_user.username = ActiveValue::Set(verify::username(username)?);
         }
+        let mut new_email = None;
         if let Some(email) = &req.email {
-            active_user.email = ActiveValue::Set(verify::email(email)?);
+            let email = verify::email(email)?;
+            if email != logged_in.email {
+                active_user.email = ActiveValue::Set(email.clone());
+                active_user.email_verified = ActiveValue::Set(false);
+                new_email = Some(email);
+            }
         }
         if let Some(password) = &req.password {
             active_user.password_hash = ActiveValue::Set(argon::encrypt(&verify::password(password)?)?);
@@ -196,9 +414,103 @@ impl User for UserController {
             Status::internal(e.to_string())
         })?;
 
+        if let Some(email) = new_email {
+            send_verification_mail(db, logged_in.id, &email).await?;
+        }
+
         Ok(Response::new(get_user_by_id(db, logged_in.id).await?.into()))
     }
 
+    /// Send a password reset token to an email address
+    ///
+    /// Does not tell if there is an account with this email
+    async fn request_password_reset(&self, request: Request<PasswordResetRequest>) -> Result<Response<Empty>, Status> {
+        let db = request.db()?;
+        let req = request.get_ref();
+
+        let user = entity::user::Entity::find()
+            .filter(entity::user::Column::Email.eq(req.email.trim().to_ascii_lowercase()))
+            .one(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        if let Some(user) = user {
+            let token = account_token::create(db, user.id, Purpose::PasswordReset).await?;
+
+            mailer::send_in_background(Mail {
+                to: user.email,
+                subject: String::from("Reset your password"),
+                body: format!(
+                    "Use this token to reset the password of {}:\n\n{token}\n\nIf you did not request this, you can ignore this mail.",
+                    user.username
+                ),
+            });
+        }
+
+        Ok(Response::new(Empty::default()))
+    }
+
+    /// Set a new password with a password reset token
+    ///
+    /// Ends all sessions of the user
+    async fn reset_password(&self, request: Request<ResetPasswordRequest>) -> Result<Response<Empty>, Status> {
+        let db = request.db()?;
+        let req = request.get_ref();
+
+        let password_hash = argon::encrypt(&verify::password(&req.password)?)?;
+        let user_id = account_token::consume(db, &req.token, Purpose::PasswordReset).await?;
+
+        entity::user::ActiveModel {
+            id: ActiveValue::Unchanged(user_id),
+            password_hash: ActiveValue::Set(password_hash),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+        entity::session::Entity::delete_many()
+            .filter(entity::session::Column::UserId.eq(user_id))
+            .exec(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(Empty::default()))
+    }
+
+    /// Send a new verification mail to the logged in user
+    async fn request_email_verification(&self, request: Request<Empty>) -> Result<Response<Empty>, Status> {
+        let logged_in = request.authorize()?;
+        let db = request.db()?;
+
+        if logged_in.email_verified {
+            return Err(Status::failed_precondition("Email is already verified"));
+        }
+
+        send_verification_mail(db, logged_in.id, &logged_in.email).await?;
+
+        Ok(Response::new(Empty::default()))
+    }
+
+    /// Verify an email address with a verification token
+    async fn verify_email(&self, request: Request<VerifyEmailRequest>) -> Result<Response<Empty>, Status> {
+        let db = request.db()?;
+        let req = request.get_ref();
+
+        let user_id = account_token::consume(db, &req.token, Purpose::EmailVerification).await?;
+
+        entity::user::ActiveModel {
+            id: ActiveValue::Unchanged(user_id),
+            email_verified: ActiveValue::Set(true),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(Empty::default()))
+    }
+
     async fn add_device_token(&self, request: Request<DeviceTokenRequest>) -> Result<Response<Empty>, Status> {
         let logged_in = request.authorize()?;
         let db = request.db()?;
@@ -239,12 +551,80 @@ impl User for UserController {
                 .update(db)
                 .await
                 .map_err(|e| Status::internal(e.to_string()))?;
+
+            entity::device_token_stat::Entity::delete_many()
+                .filter(entity::device_token_stat::Column::UserId.eq(logged_in.id))
+                .filter(entity::device_token_stat::Column::Token.eq(&req.token))
+                .exec(db)
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?;
         } else {
             // TODO: Should it throw a 404?
         }
 
         Ok(Response::new(Empty::default()))
     }
+
+    /// Delete the logged in user
+    ///
+    /// Readings, follows and chapter offsets are removed by the database cascades
+    async fn delete(&self, request: Request<UserDeleteRequest>) -> Result<Response<Empty>, Status> {
+        let logged_in = request.authorize()?;
+        let db = request.db()?;
+        let req = request.get_ref();
+
+        argon::verify(&logged_in.password_hash, &req.password)?;
+
+        entity::user::Entity::delete_by_id(logged_in.id)
+            .exec(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        info!("Deleted account of user '{}'", logged_in.username);
+
+        Ok(Response::new(Empty::default()))
+    }
+
+    /// Download all data of the logged in user as a JSON archive
+    async fn export(&self, request: Request<Empty>) -> Result<Response<Self::ExportStream>, Status> {
+        let logged_in = request.authorize()?;
+        let db = request.db()?;
+
+        let archive = export_archive(db, logged_in).await?;
+        let bytes = serde_json::to_vec_pretty(&archive).map_err(|e| Status::internal(e.to_string()))?;
+
+        let chunks: Vec<Result<UserExportChunk, Status>> = bytes
+            .chunks(EXPORT_CHUNK_SIZE)
+            .map(|chunk| Ok(UserExportChunk { data: chunk.to_vec() }))
+            .collect();
+
+        Ok(Response::new(Box::pin(tokio_stream::iter(chunks)) as Self::ExportStream))
+    }
 }
 
-crate::export_service!(UserServer, UserController);
+crate::export_service!(
+    UserServer,
+    UserController,
+    auth = {
+        "Logout" => UserPermissions::USER,
+        "Sessions" => UserPermissions::USER,
+        "RevokeSession" => UserPermissions::USER,
+        "Me" => UserPermissions::USER,
+        "Update" => UserPermissions::USER,
+        "RequestEmailVerification" => UserPermissions::USER,
+        "AddDeviceToken" => UserPermissions::USER,
+        "RemoveDeviceToken" => UserPermissions::USER,
+        "Delete" => UserPermissions::USER,
+        "Export" => UserPermissions::USER,
+    },
+    public = [
+        "Register",
+        "Login",
+        "Refresh",
+        "RequestPasswordReset",
+        "ResetPassword",
+        "VerifyEmail",
+        "Get",
+        "Index",
+    ]
+);
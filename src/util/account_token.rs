@@ -0,0 +1,93 @@
+use chrono::Utc;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use tonic::Status;
+
+use crate::util::token::{generate, hash};
+
+/// What a single-use account token can be used for
+#[derive(Debug, Clone, Copy)]
+pub enum Purpose {
+    PasswordReset,
+    EmailVerification,
+}
+
+impl Purpose {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Purpose::PasswordReset => "password_reset",
+            Purpose::EmailVerification => "email_verification",
+        }
+    }
+
+    /// How long a token stays valid
+    fn ttl_ms(&self) -> i64 {
+        let (var, default) = match self {
+            Purpose::PasswordReset => ("PASSWORD_RESET_TOKEN_TTL_MS", 3600000),
+            Purpose::EmailVerification => ("EMAIL_VERIFICATION_TOKEN_TTL_MS", 86400000),
+        };
+
+        std::env::var(var)
+            .unwrap_or(default.to_string())
+            .parse()
+            .unwrap_or(default)
+    }
+}
+
+/// Create a new token for a user
+///
+/// Older tokens with the same purpose are removed
+pub async fn create(db: &DatabaseConnection, user_id: i32, purpose: Purpose) -> Result<String, Status> {
+    use entity::account_token::Column;
+
+    entity::account_token::Entity::delete_many()
+        .filter(Column::UserId.eq(user_id))
+        .filter(Column::Purpose.eq(purpose.as_str()))
+        .exec(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    let token = generate();
+
+    entity::account_token::ActiveModel {
+        user_id: Set(user_id),
+        purpose: Set(purpose.as_str().to_string()),
+        token_hash: Set(hash(&token)),
+        expires_at: Set((Utc::now() + chrono::Duration::milliseconds(purpose.ttl_ms())).naive_utc()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(token)
+}
+
+/// Use a token, it can not be used again afterwards
+///
+/// Returns the id of the user the token belongs to
+pub async fn consume(db: &DatabaseConnection, token: &str, purpose: Purpose) -> Result<i32, Status> {
+    use entity::account_token::Column;
+
+    let error = Status::invalid_argument("Token is invalid or has expired");
+
+    let stored = entity::account_token::Entity::find()
+        .filter(Column::TokenHash.eq(hash(token)))
+        .filter(Column::Purpose.eq(purpose.as_str()))
+        .one(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .ok_or(error.clone())?;
+
+    // Delete it first, so it can only be used once
+    let res = entity::account_token::Entity::delete_by_id(stored.id)
+        .exec(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    if res.rows_affected == 0 || stored.expires_at < Utc::now().naive_utc() {
+        return Err(error);
+    }
+
+    Ok(stored.user_id)
+}
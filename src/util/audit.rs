@@ -0,0 +1,31 @@
+use sea_orm::ActiveValue::Set;
+use sea_orm::{ActiveModelTrait, DatabaseConnection};
+use serde_json::Value;
+use tonic::Status;
+
+/// Record an action taken by an admin
+pub async fn record(
+    db: &DatabaseConnection,
+    actor: &entity::user::Model,
+    action: &str,
+    target_id: i32,
+    details: Value,
+) -> Result<(), Status> {
+    info!(
+        "[Audit] {} ({}) {} {}: {}",
+        actor.username, actor.id, action, target_id, details
+    );
+
+    entity::audit_log::ActiveModel {
+        actor_id: Set(Some(actor.id)),
+        action: Set(action.to_string()),
+        target_id: Set(target_id),
+        details: Set(details),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(())
+}
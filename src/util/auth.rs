@@ -1,7 +1,10 @@
 use tonic::{Request, Status};
 
+use crate::interceptor::auth::Token;
+
 pub trait Authorize {
     fn authorize(&self) -> Result<&entity::user::Model, Status>;
+    fn token(&self) -> Result<&Token, Status>;
 }
 
 impl<T> Authorize for Request<T> {
@@ -10,4 +13,10 @@ impl<T> Authorize for Request<T> {
             .get::<entity::user::Model>()
             .ok_or(Status::unauthenticated("Missing bearer token! Log in first"))
     }
+
+    fn token(&self) -> Result<&Token, Status> {
+        self.extensions()
+            .get::<Token>()
+            .ok_or(Status::unauthenticated("Missing bearer token! Log in first"))
+    }
 }
@@ -0,0 +1,36 @@
+use prost::bytes::BytesMut;
+use prost::Message;
+use tonic::Status;
+
+use crate::proto::{self, AuthErrorType};
+
+impl proto::AuthError {
+    pub fn new(r#type: AuthErrorType, message: &str) -> Self {
+        Self {
+            r#type: r#type.into(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<proto::AuthError> for Status {
+    fn from(value: proto::AuthError) -> Self {
+        let message = value.message.clone();
+        let mut buffer = BytesMut::with_capacity(1024);
+        value.encode(&mut buffer).expect("encode error");
+
+        let details = proto::DetailedError {
+            status: tonic::Code::Unauthenticated as i32,
+            message: message.clone(),
+            details: vec![prost_types::Any {
+                type_url: "rumgap.v1.AuthError".to_string(),
+                value: buffer.to_vec(),
+            }],
+        };
+
+        buffer.clear();
+        details.encode(&mut buffer).expect("encode error");
+
+        Status::with_details(tonic::Code::Unauthenticated, message, buffer.into())
+    }
+}
@@ -0,0 +1,29 @@
+use tokio::sync::broadcast;
+
+lazy_static! {
+    /// Updates that were not received yet by a slow watcher before it misses them
+    static ref BUFFER: usize = std::env::var("MANGA_WATCH_BUFFER")
+        .unwrap_or("256".to_string())
+        .parse()
+        .unwrap_or(256);
+    /// Manga updates of this server, for clients that are watching
+    static ref BUS: broadcast::Sender<MangaUpdate> = broadcast::channel(BUFFER.max(1)).0;
+}
+
+/// New chapters that were saved for a manga
+#[derive(Debug, Clone)]
+pub struct MangaUpdate {
+    pub manga_id: i32,
+    /// In reading order
+    pub chapters: Vec<entity::chapter::Model>,
+}
+
+/// Tell all watchers about an update, nobody watching is fine
+pub fn publish(update: MangaUpdate) {
+    let _ = BUS.send(update);
+}
+
+/// Receive all updates that are published from now on
+pub fn subscribe() -> broadcast::Receiver<MangaUpdate> {
+    BUS.subscribe()
+}
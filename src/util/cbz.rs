@@ -0,0 +1,152 @@
+use std::fmt::Write;
+
+use flate2::Crc;
+
+/// A downloaded page of a chapter
+pub struct Page {
+    pub data: Vec<u8>,
+    pub content_type: &'static str,
+}
+
+/// Escape text for use in XML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// File extension of a page by its type
+fn extension(content_type: &str) -> &'static str {
+    match content_type {
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/avif" => "avif",
+        _ => "jpg",
+    }
+}
+
+/// Name of the archive of a chapter, without characters that are not allowed in file names
+pub fn file_name(manga: &entity::manga::Model, chapter: &entity::chapter::Model) -> String {
+    let name = format!("{} - Ch. {}.cbz", manga.title, chapter.number);
+    name.chars()
+        .map(|c| {
+            if "\\/:*?\"<>|".contains(c) || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// ComicInfo.xml as read by most comic readers
+pub fn comic_info(manga: &entity::manga::Model, chapter: &entity::chapter::Model, page_count: usize) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<ComicInfo xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\">\n");
+    let _ = writeln!(xml, "\t<Title>{}</Title>", escape(&chapter.title));
+    let _ = writeln!(xml, "\t<Series>{}</Series>", escape(&manga.title));
+    let _ = writeln!(xml, "\t<Number>{}</Number>", chapter.number);
+    let _ = writeln!(xml, "\t<Summary>{}</Summary>", escape(&manga.description));
+    if let Some(posted) = chapter.posted {
+        let _ = writeln!(xml, "\t<Year>{}</Year>", posted.format("%Y"));
+        let _ = writeln!(xml, "\t<Month>{}</Month>", posted.format("%-m"));
+        let _ = writeln!(xml, "\t<Day>{}</Day>", posted.format("%-d"));
+    }
+    if !manga.authors.is_empty() {
+        let _ = writeln!(xml, "\t<Writer>{}</Writer>", escape(&manga.authors.join(", ")));
+    }
+    if !manga.genres.is_empty() {
+        let _ = writeln!(xml, "\t<Genre>{}</Genre>", escape(&manga.genres.join(", ")));
+    }
+    let _ = writeln!(xml, "\t<Web>{}</Web>", escape(&chapter.url));
+    let _ = writeln!(xml, "\t<PageCount>{}</PageCount>", page_count);
+    xml.push_str("\t<Manga>Yes</Manga>\n");
+    xml.push_str("</ComicInfo>\n");
+    xml
+}
+
+/// Write a CBZ archive (a ZIP without compression, images do not compress anyway)
+pub fn write(comic_info: &str, pages: &[Page]) -> Vec<u8> {
+    let mut files: Vec<(String, &[u8])> = vec![("ComicInfo.xml".to_string(), comic_info.as_bytes())];
+    for (index, page) in pages.iter().enumerate() {
+        files.push((format!("{:04}.{}", index + 1, extension(page.content_type)), &page.data));
+    }
+
+    let mut zip = vec![];
+    let mut central = vec![];
+
+    for (name, data) in &files {
+        let mut crc = Crc::new();
+        crc.update(data);
+        let offset = zip.len() as u32;
+
+        // Modified at 1980-01-01 00:00, the first date ZIP supports
+        let header = |zip: &mut Vec<u8>| {
+            zip.extend_from_slice(&20u16.to_le_bytes()); // Version needed
+            zip.extend_from_slice(&0x0800u16.to_le_bytes()); // UTF-8 names
+            zip.extend_from_slice(&0u16.to_le_bytes()); // Stored
+            zip.extend_from_slice(&0u16.to_le_bytes()); // Time
+            zip.extend_from_slice(&0x21u16.to_le_bytes()); // Date
+            zip.extend_from_slice(&crc.sum().to_le_bytes());
+            zip.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            zip.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            zip.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            zip.extend_from_slice(&0u16.to_le_bytes()); // Extra field
+        };
+
+        zip.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        header(&mut zip);
+        zip.extend_from_slice(name.as_bytes());
+        zip.extend_from_slice(data);
+
+        central.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes()); // Version made by
+        header(&mut central);
+        central.extend_from_slice(&0u16.to_le_bytes()); // Comment
+        central.extend_from_slice(&0u16.to_le_bytes()); // Disk
+        central.extend_from_slice(&0u16.to_le_bytes()); // Internal attributes
+        central.extend_from_slice(&0u32.to_le_bytes()); // External attributes
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+    }
+
+    let central_offset = zip.len() as u32;
+    zip.extend_from_slice(&central);
+
+    zip.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    zip.extend_from_slice(&0u16.to_le_bytes()); // Disk
+    zip.extend_from_slice(&0u16.to_le_bytes()); // Disk with the central directory
+    zip.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    zip.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    zip.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    zip.extend_from_slice(&central_offset.to_le_bytes());
+    zip.extend_from_slice(&0u16.to_le_bytes()); // Comment
+
+    zip
+}
+
+#[test]
+fn write_stored_zip() {
+    let pages = [Page {
+        data: b"123456789".to_vec(),
+        content_type: "image/png",
+    }];
+    let zip = write("<ComicInfo/>", &pages);
+
+    // First file is the ComicInfo.xml
+    assert_eq!(&zip[..4], &0x04034b50u32.to_le_bytes());
+    assert_eq!(&zip[30..43], b"ComicInfo.xml");
+
+    // CRC-32 of the page
+    let page = 30 + 13 + 12;
+    assert_eq!(&zip[page + 14..page + 18], &0xCBF43926u32.to_le_bytes());
+    assert_eq!(&zip[page + 30..page + 38], b"0001.png");
+
+    // End of central directory with 2 files
+    let end = zip.len() - 22;
+    assert_eq!(&zip[end..end + 4], &0x06054b50u32.to_le_bytes());
+    assert_eq!(&zip[end + 10..end + 12], &2u16.to_le_bytes());
+}
@@ -0,0 +1,226 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::ActiveValue::{Set, Unchanged};
+
+/// A chapter as found by the scraper
+#[derive(Debug, Clone)]
+pub struct Scraped {
+    pub url: String,
+    pub title: String,
+    pub number: f32,
+    pub posted: Option<DateTimeWithTimeZone>,
+}
+
+impl From<&manga_parser::model::Chapter> for Scraped {
+    fn from(value: &manga_parser::model::Chapter) -> Self {
+        Self {
+            url: value.url.to_string(),
+            title: value.title.clone(),
+            number: value.number,
+            posted: value.date.map(|date| date.into()),
+        }
+    }
+}
+
+/// Changes that make the saved chapters of a manga match the scraped ones
+#[derive(Debug, Default)]
+pub struct ChapterDiff {
+    /// Amount of chapters that did not change
+    pub kept: usize,
+    /// Chapters with a new URL, title, number, date or position, or that were deleted before
+    pub updated: Vec<entity::chapter::ActiveModel>,
+    /// IDs of updated chapters with a new URL, these may have other images now
+    pub relinked: Vec<i32>,
+    /// Amount of updated chapters that were deleted before
+    pub restored: usize,
+    /// IDs of chapters that are not found anymore, these should be soft-deleted
+    pub deleted: Vec<i32>,
+    /// Chapters that are new
+    pub inserted: Vec<entity::chapter::ActiveModel>,
+}
+
+/// Dates are saved with microsecond precision
+fn same_date(a: Option<DateTimeWithTimeZone>, b: Option<DateTimeWithTimeZone>) -> bool {
+    a.map(|date| date.timestamp_micros()) == b.map(|date| date.timestamp_micros())
+}
+
+/// Match scraped chapters to saved chapters by URL, or by number if the URL changed
+///
+/// `saved` includes soft-deleted chapters, `scraped` is in reading order and every chapter gets its index as position
+pub fn diff(manga_id: i32, saved: &[entity::chapter::Model], scraped: &[Scraped]) -> ChapterDiff {
+    let scraped_urls: HashSet<&str> = scraped.iter().map(|chapter| chapter.url.as_str()).collect();
+    let by_url: HashMap<&str, &entity::chapter::Model> =
+        saved.iter().map(|chapter| (chapter.url.as_str(), chapter)).collect();
+
+    // Saved chapters whose URL is gone can still be matched by their number, in reading order
+    let mut by_number: HashMap<u32, VecDeque<&entity::chapter::Model>> = HashMap::new();
+    for chapter in saved
+        .iter()
+        .filter(|chapter| !scraped_urls.contains(chapter.url.as_str()))
+    {
+        by_number
+            .entry(chapter.number.to_bits())
+            .or_default()
+            .push_back(chapter);
+    }
+
+    let mut matched = HashSet::new();
+    let mut diff = ChapterDiff::default();
+
+    for (position, chapter) in scraped.iter().enumerate() {
+        let position = position as i32;
+        let existing = by_url
+            .get(chapter.url.as_str())
+            .copied()
+            .filter(|existing| !matched.contains(&existing.id))
+            .or_else(|| {
+                by_number
+                    .get_mut(&chapter.number.to_bits())
+                    .and_then(VecDeque::pop_front)
+            });
+
+        let Some(existing) = existing else {
+            diff.inserted.push(entity::chapter::ActiveModel {
+                manga_id: Set(manga_id),
+                number: Set(chapter.number),
+                url: Set(chapter.url.clone()),
+                title: Set(chapter.title.clone()),
+                posted: Set(chapter.posted),
+                position: Set(position),
+                ..Default::default()
+            });
+            continue;
+        };
+        matched.insert(existing.id);
+
+        if existing.url == chapter.url
+            && existing.title == chapter.title
+            && existing.number == chapter.number
+            && same_date(existing.posted, chapter.posted)
+            && existing.position == position
+            && existing.deleted_at.is_none()
+        {
+            diff.kept += 1;
+            continue;
+        }
+
+        if existing.deleted_at.is_some() {
+            diff.restored += 1;
+        }
+        if existing.url != chapter.url {
+            diff.relinked.push(existing.id);
+        }
+        diff.updated.push(entity::chapter::ActiveModel {
+            id: Unchanged(existing.id),
+            number: Set(chapter.number),
+            url: Set(chapter.url.clone()),
+            title: Set(chapter.title.clone()),
+            posted: Set(chapter.posted),
+            position: Set(position),
+            deleted_at: Set(None),
+            ..Default::default()
+        });
+    }
+
+    diff.deleted = saved
+        .iter()
+        .filter(|chapter| chapter.deleted_at.is_none() && !matched.contains(&chapter.id))
+        .map(|chapter| chapter.id)
+        .collect();
+
+    diff
+}
+
+#[test]
+fn diff_by_url_and_number() {
+    let now = chrono::Utc::now().naive_utc();
+    let saved = |id: i32, number: f32, deleted: bool| entity::chapter::Model {
+        id,
+        manga_id: 1,
+        url: format!("https://example.com/{id}"),
+        title: format!("Chapter {number}"),
+        number,
+        posted: None,
+        created_at: now,
+        updated_at: now,
+        deleted_at: deleted.then_some(now),
+        position: id - 1,
+        images_fetched_at: None,
+    };
+    let scraped = |url: &str, number: f32| Scraped {
+        url: url.to_string(),
+        title: format!("Chapter {number}"),
+        number,
+        posted: None,
+    };
+
+    let diff = diff(
+        1,
+        &[
+            saved(1, 1.0, false),
+            saved(2, 2.0, false),
+            saved(3, 3.0, false),
+            saved(4, 4.0, true),
+        ],
+        &[
+            // Unchanged
+            scraped("https://example.com/1", 1.0),
+            // New URL, same number
+            scraped("https://example.com/new-2", 2.0),
+            // Found again
+            scraped("https://example.com/4", 4.0),
+            // New
+            scraped("https://example.com/5", 5.0),
+        ],
+    );
+
+    assert_eq!(diff.kept, 1);
+    assert_eq!(diff.updated.len(), 2);
+    assert_eq!(diff.restored, 1);
+    assert_eq!(diff.deleted, vec![3]);
+    assert_eq!(diff.inserted.len(), 1);
+    assert_eq!(diff.relinked, vec![2]);
+}
+
+#[test]
+fn diff_moves_chapters() {
+    let now = chrono::Utc::now().naive_utc();
+    let saved = |id: i32| entity::chapter::Model {
+        id,
+        manga_id: 1,
+        url: format!("https://example.com/{id}"),
+        title: format!("Chapter {id}"),
+        number: id as f32,
+        posted: None,
+        created_at: now,
+        updated_at: now,
+        deleted_at: None,
+        position: id - 1,
+        images_fetched_at: None,
+    };
+    let scraped = |id: i32| Scraped {
+        url: format!("https://example.com/{id}"),
+        title: format!("Chapter {id}"),
+        number: id as f32,
+        posted: None,
+    };
+
+    // A chapter shows up between the first and second chapter
+    let diff = diff(
+        1,
+        &[saved(1), saved(2), saved(3)],
+        &[scraped(1), scraped(4), scraped(2), scraped(3)],
+    );
+
+    assert_eq!(diff.kept, 1);
+    assert_eq!(
+        diff.updated
+            .iter()
+            .map(|chapter| (chapter.id.clone().unwrap(), chapter.position.clone().unwrap()))
+            .collect::<Vec<_>>(),
+        vec![(2, 2), (3, 3)]
+    );
+    assert!(diff.relinked.is_empty());
+    assert_eq!(diff.inserted[0].position.clone().unwrap(), 1);
+}
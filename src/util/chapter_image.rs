@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use chrono::{Duration, Utc};
+use manga_parser::Url;
+use sea_orm::prelude::{DateTime, Expr};
+use sea_orm::ActiveValue::{Set, Unchanged};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, TransactionTrait,
+};
+use tonic::Status;
+
+use crate::util::scrape_error_proto::StatusWrapper;
+use crate::MANGA_PARSER;
+
+lazy_static! {
+    /// Scrape the images of a chapter again when they were scraped longer than this ago (default 6 hours)
+    static ref IMAGES_TTL_MS: i64 = std::env::var("CHAPTER_IMAGES_TTL_MS")
+        .unwrap_or("21600000".to_string())
+        .parse()
+        .unwrap_or(21600000);
+    /// Chapters of which the images are being scraped in the background
+    static ref REFRESHING: Mutex<HashSet<i32>> = Mutex::default();
+}
+
+/// Save the images of a chapter, replacing the old ones
+///
+/// The chapter remembers when they were saved, so a chapter without images is not scraped again every time
+async fn save(db: &DatabaseConnection, chapter_id: i32, images: &[Url]) -> Result<(), Status> {
+    let fetched_at = Utc::now().naive_utc();
+
+    let txn = db.begin().await.map_err(|e| Status::internal(e.to_string()))?;
+
+    entity::chapter_image::Entity::delete_many()
+        .filter(entity::chapter_image::Column::ChapterId.eq(chapter_id))
+        .exec(&txn)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    if !images.is_empty() {
+        entity::chapter_image::Entity::insert_many(images.iter().enumerate().map(|(position, url)| {
+            entity::chapter_image::ActiveModel {
+                chapter_id: Set(chapter_id),
+                position: Set(position as i32),
+                url: Set(url.to_string()),
+                fetched_at: Set(fetched_at),
+                ..Default::default()
+            }
+        }))
+        .exec_without_returning(&txn)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+    }
+
+    entity::chapter::ActiveModel {
+        id: Unchanged(chapter_id),
+        images_fetched_at: Set(Some(fetched_at)),
+        ..Default::default()
+    }
+    .update(&txn)
+    .await
+    .map_err(|e| Status::internal(e.to_string()))?;
+
+    txn.commit().await.map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Scrape the images of a chapter and save them
+pub async fn refresh(db: &DatabaseConnection, chapter: &entity::chapter::Model) -> Result<Vec<Url>, Status> {
+    let url = Url::parse(&chapter.url).map_err(|e| Status::internal(e.to_string()))?;
+
+    let images = MANGA_PARSER
+        .chapter_images(&url)
+        .await
+        .map_err(|e| Status::from(StatusWrapper::from(e)))?;
+
+    save(db, chapter.id, &images).await?;
+
+    Ok(images)
+}
+
+/// Scrape the images of a chapter in the background, unless that is already happening
+fn refresh_in_background(db: &DatabaseConnection, chapter: &entity::chapter::Model) {
+    if !REFRESHING.lock().unwrap().insert(chapter.id) {
+        return;
+    }
+
+    let db = db.clone();
+    let chapter = chapter.clone();
+    tokio::spawn(async move {
+        if let Err(e) = refresh(&db, &chapter).await {
+            warn!("Failed to refresh images of chapter {}: {}", chapter.id, e.message());
+        }
+        REFRESHING.lock().unwrap().remove(&chapter.id);
+    });
+}
+
+/// Images of a chapter in reading order, scraped only if they were never saved before
+///
+/// Images older than `CHAPTER_IMAGES_TTL_MS` are still returned, but scraped again in the background
+pub async fn images(db: &DatabaseConnection, chapter: &entity::chapter::Model) -> Result<Vec<Url>, Status> {
+    let Some(fetched_at) = chapter.images_fetched_at else {
+        return refresh(db, chapter).await;
+    };
+
+    let saved = entity::chapter_image::Entity::find()
+        .filter(entity::chapter_image::Column::ChapterId.eq(chapter.id))
+        .order_by_asc(entity::chapter_image::Column::Position)
+        .all(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    if fetched_at + Duration::milliseconds(*IMAGES_TTL_MS) < Utc::now().naive_utc() {
+        refresh_in_background(db, chapter);
+    }
+
+    Ok(saved
+        .into_iter()
+        .filter_map(|image| Url::parse(&image.url).ok())
+        .collect())
+}
+
+/// Forget the saved images of chapters, so they are scraped again the next time
+pub async fn forget(db: &DatabaseConnection, chapter_ids: Vec<i32>) -> Result<(), Status> {
+    if chapter_ids.is_empty() {
+        return Ok(());
+    }
+
+    entity::chapter_image::Entity::delete_many()
+        .filter(entity::chapter_image::Column::ChapterId.is_in(chapter_ids.clone()))
+        .exec(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    entity::chapter::Entity::update_many()
+        .col_expr(
+            entity::chapter::Column::ImagesFetchedAt,
+            Expr::value(Option::<DateTime>::None),
+        )
+        .filter(entity::chapter::Column::Id.is_in(chapter_ids))
+        .exec(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(())
+}
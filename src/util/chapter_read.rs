@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+
+use migration::OnConflict;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DbBackend, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect,
+    Statement,
+};
+use tonic::Status;
+
+/// Set the progress of every reading of a manga to the amount of chapters read
+const SYNC_PROGRESS_QUERY: &str = r#"
+UPDATE reading SET progress = (
+    SELECT COUNT(*) FROM chapter_read
+    JOIN chapter ON chapter.id = chapter_read.chapter_id
+    WHERE chapter.manga_id = reading.manga_id AND chapter_read.user_id = reading.user_id AND chapter.deleted_at IS NULL
+)
+WHERE reading.manga_id = $1"#;
+
+/// IDs of all chapters of a manga in reading order
+pub async fn chapter_ids<C: ConnectionTrait>(db: &C, manga_id: i32) -> Result<Vec<i32>, Status> {
+    entity::chapter::Entity::find()
+        .select_only()
+        .column(entity::chapter::Column::Id)
+        .filter(entity::chapter::Column::MangaId.eq(manga_id))
+        .filter(entity::chapter::Column::DeletedAt.is_null())
+        .order_by_asc(entity::chapter::Column::Position)
+        .into_tuple()
+        .all(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))
+}
+
+/// IDs of the chapters of a manga from one chapter up to and including another, in reading order
+pub async fn chapter_range<C: ConnectionTrait>(
+    db: &C,
+    manga_id: i32,
+    from_chapter_id: i32,
+    to_chapter_id: i32,
+) -> Result<Vec<i32>, Status> {
+    let positions: Vec<i32> = entity::chapter::Entity::find()
+        .select_only()
+        .column(entity::chapter::Column::Position)
+        .filter(entity::chapter::Column::MangaId.eq(manga_id))
+        .filter(entity::chapter::Column::DeletedAt.is_null())
+        .filter(entity::chapter::Column::Id.is_in([from_chapter_id, to_chapter_id]))
+        .into_tuple()
+        .all(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    // Both ends have to be chapters of this manga
+    let ends = if from_chapter_id == to_chapter_id { 1 } else { 2 };
+    if positions.len() != ends {
+        return Err(Status::not_found("Chapter not found"));
+    }
+    let (first, last) = (positions.iter().min().unwrap(), positions.iter().max().unwrap());
+
+    entity::chapter::Entity::find()
+        .select_only()
+        .column(entity::chapter::Column::Id)
+        .filter(entity::chapter::Column::MangaId.eq(manga_id))
+        .filter(entity::chapter::Column::DeletedAt.is_null())
+        .filter(entity::chapter::Column::Position.between(*first, *last))
+        .order_by_asc(entity::chapter::Column::Position)
+        .into_tuple()
+        .all(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))
+}
+
+/// Mark chapters of a manga as read or unread and update the progress of the reading
+///
+/// Returns the chapters that changed and the new progress
+pub async fn mark<C: ConnectionTrait>(
+    db: &C,
+    user_id: i32,
+    manga_id: i32,
+    chapter_ids: &[i32],
+    read: bool,
+) -> Result<(Vec<i32>, i32), Status> {
+    use entity::chapter_read::Column;
+
+    let already_read: HashSet<i32> = entity::chapter_read::Entity::find()
+        .select_only()
+        .column(Column::ChapterId)
+        .filter(Column::UserId.eq(user_id))
+        .filter(Column::ChapterId.is_in(chapter_ids.iter().copied()))
+        .into_tuple::<i32>()
+        .all(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .into_iter()
+        .collect();
+
+    let changed: Vec<i32> = chapter_ids
+        .iter()
+        .copied()
+        .filter(|chapter_id| already_read.contains(chapter_id) != read)
+        .collect();
+
+    if changed.is_empty() {
+        // Nothing to do
+    } else if read {
+        entity::chapter_read::Entity::insert_many(changed.iter().map(|chapter_id| entity::chapter_read::ActiveModel {
+            user_id: Set(user_id),
+            chapter_id: Set(*chapter_id),
+            ..Default::default()
+        }))
+        .on_conflict(
+            OnConflict::columns([Column::UserId, Column::ChapterId])
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+    } else {
+        entity::chapter_read::Entity::delete_many()
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::ChapterId.is_in(changed.iter().copied()))
+            .exec(db)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+    }
+
+    let progress = entity::chapter_read::Entity::find()
+        .inner_join(entity::chapter::Entity)
+        .filter(Column::UserId.eq(user_id))
+        .filter(entity::chapter::Column::MangaId.eq(manga_id))
+        .filter(entity::chapter::Column::DeletedAt.is_null())
+        .count(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))? as i32;
+
+    entity::reading::Entity::update_many()
+        .col_expr(entity::reading::Column::Progress, progress.into())
+        .filter(entity::reading::Column::UserId.eq(user_id))
+        .filter(entity::reading::Column::MangaId.eq(manga_id))
+        .exec(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok((changed, progress))
+}
+
+/// Update the progress of all readings of a manga after its chapters changed
+pub async fn sync_progress<C: ConnectionTrait>(db: &C, manga_id: i32) -> Result<(), Status> {
+    db.execute(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        SYNC_PROGRESS_QUERY,
+        [manga_id.into()],
+    ))
+    .await
+    .map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use sea_orm::ActiveEnum;
+
+use super::Entry;
+
+const HEADER: [&str; 10] = [
+    "title",
+    "url",
+    "status",
+    "progress",
+    "chapters",
+    "last_chapter_url",
+    "last_chapter_number",
+    "offset",
+    "page",
+    "updated_at",
+];
+
+/// Quote a field if it contains characters that have a meaning in CSV
+fn field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Write a CSV file with one row per manga
+///
+/// The last chapter is the one with the most recently updated chapter offset
+pub fn write(entries: &[Entry]) -> Vec<u8> {
+    let mut csv = HEADER.join(",");
+    csv.push_str("\r\n");
+
+    for entry in entries {
+        let last = entry.last_offset();
+        let row = [
+            field(&entry.manga.title),
+            field(&entry.manga.url),
+            entry.status.to_value(),
+            entry.progress.to_string(),
+            entry.chapters.len().to_string(),
+            last.map_or(String::new(), |(chapter, _offset)| field(&chapter.url)),
+            last.map_or(String::new(), |(chapter, _offset)| chapter.number.to_string()),
+            last.map_or(String::new(), |(_chapter, offset)| offset.offset.to_string()),
+            last.map_or(String::new(), |(_chapter, offset)| offset.page.to_string()),
+            last.map_or(String::new(), |(_chapter, offset)| {
+                offset.updated_at.and_utc().to_rfc3339()
+            }),
+        ];
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+
+    csv.into_bytes()
+}
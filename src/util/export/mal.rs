@@ -0,0 +1,48 @@
+use std::fmt::Write;
+
+use entity::sea_orm_active_enums::ReadingStatus;
+
+use super::Entry;
+
+/// Wrap text in CDATA, splitting up "]]>" so it can not end the section
+fn cdata(text: &str) -> String {
+    format!("<![CDATA[{}]]>", text.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+/// Status as used by MyAnimeList
+fn status(status: ReadingStatus) -> &'static str {
+    match status {
+        ReadingStatus::Reading => "Reading",
+        ReadingStatus::PlanToRead => "Plan to Read",
+        ReadingStatus::OnHold => "On-Hold",
+        ReadingStatus::Dropped => "Dropped",
+        ReadingStatus::Completed => "Completed",
+    }
+}
+
+/// Write a MyAnimeList compatible XML export
+pub fn write(username: &str, entries: &[Entry]) -> Vec<u8> {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" ?>\n<myanimelist>\n");
+    xml.push_str("\t<myinfo>\n");
+    let _ = writeln!(xml, "\t\t<user_name>{}</user_name>", cdata(username));
+    xml.push_str("\t\t<user_export_type>2</user_export_type>\n");
+    let _ = writeln!(xml, "\t\t<user_total_manga>{}</user_total_manga>", entries.len());
+    xml.push_str("\t</myinfo>\n");
+
+    for entry in entries {
+        xml.push_str("\t<manga>\n");
+        xml.push_str("\t\t<manga_mangadb_id>0</manga_mangadb_id>\n");
+        let _ = writeln!(xml, "\t\t<manga_title>{}</manga_title>", cdata(&entry.manga.title));
+        xml.push_str("\t\t<manga_volumes>0</manga_volumes>\n");
+        let _ = writeln!(xml, "\t\t<manga_chapters>{}</manga_chapters>", entry.chapters.len());
+        xml.push_str("\t\t<my_read_volumes>0</my_read_volumes>\n");
+        let _ = writeln!(xml, "\t\t<my_read_chapters>{}</my_read_chapters>", entry.progress);
+        let _ = writeln!(xml, "\t\t<my_status>{}</my_status>", status(entry.status));
+        let _ = writeln!(xml, "\t\t<my_comments>{}</my_comments>", cdata(&entry.manga.url));
+        xml.push_str("\t\t<update_on_import>1</update_on_import>\n");
+        xml.push_str("\t</manga>\n");
+    }
+
+    xml.push_str("</myanimelist>\n");
+    xml.into_bytes()
+}
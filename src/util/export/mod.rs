@@ -0,0 +1,120 @@
+use std::collections::{HashMap, HashSet};
+
+use entity::sea_orm_active_enums::ReadingStatus;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder};
+use tonic::Status;
+
+use crate::proto::{ExportFormat, ReadingExportReply};
+
+pub mod csv;
+pub mod mal;
+pub mod tachiyomi;
+
+/// A manga on the reading list of a user
+pub struct Entry {
+    pub manga: entity::manga::Model,
+    /// Amount of chapters read
+    pub progress: i32,
+    pub status: ReadingStatus,
+    /// Chapters in reading order
+    pub chapters: Vec<entity::chapter::Model>,
+    /// Chapter offsets by chapter ID
+    pub offsets: HashMap<i32, entity::chapter_offset::Model>,
+    /// IDs of the chapters that were read
+    pub read: HashSet<i32>,
+}
+
+impl Entry {
+    /// The chapter that was read most recently, if the user has an offset for it
+    pub fn last_offset(&self) -> Option<(&entity::chapter::Model, &entity::chapter_offset::Model)> {
+        self.chapters
+            .iter()
+            .filter_map(|chapter| Some((chapter, self.offsets.get(&chapter.id)?)))
+            .max_by_key(|(_chapter, offset)| offset.updated_at)
+    }
+}
+
+/// Get the reading list of a user with chapters, offsets and read chapters
+async fn entries(db: &DatabaseConnection, user_id: i32) -> Result<Vec<Entry>, Status> {
+    let readings = entity::reading::Entity::find()
+        .filter(entity::reading::Column::UserId.eq(user_id))
+        .find_also_related(entity::manga::Entity)
+        .order_by_asc(entity::manga::Column::Title)
+        .all(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    let mut chapters: HashMap<i32, Vec<entity::chapter::Model>> = HashMap::new();
+    for chapter in entity::chapter::Entity::find()
+        .filter(entity::chapter::Column::MangaId.is_in(readings.iter().map(|(reading, _manga)| reading.manga_id)))
+        .filter(entity::chapter::Column::DeletedAt.is_null())
+        .order_by_asc(entity::chapter::Column::Position)
+        .all(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+    {
+        chapters.entry(chapter.manga_id).or_default().push(chapter);
+    }
+
+    let mut offsets: HashMap<i32, entity::chapter_offset::Model> = entity::chapter_offset::Entity::find()
+        .filter(entity::chapter_offset::Column::UserId.eq(user_id))
+        .all(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .into_iter()
+        .map(|offset| (offset.chapter_id, offset))
+        .collect();
+
+    let mut read: HashSet<i32> = entity::chapter_read::Entity::find()
+        .filter(entity::chapter_read::Column::UserId.eq(user_id))
+        .all(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .into_iter()
+        .map(|chapter_read| chapter_read.chapter_id)
+        .collect();
+
+    Ok(readings
+        .into_iter()
+        .filter_map(|(reading, manga)| {
+            let manga = manga?;
+            let chapters = chapters.remove(&manga.id).unwrap_or_default();
+            let offsets = chapters
+                .iter()
+                .filter_map(|chapter| offsets.remove_entry(&chapter.id))
+                .collect();
+            let read = chapters.iter().filter_map(|chapter| read.take(&chapter.id)).collect();
+
+            Some(Entry {
+                manga,
+                progress: reading.progress,
+                status: reading.status,
+                chapters,
+                offsets,
+                read,
+            })
+        })
+        .collect())
+}
+
+/// Export the reading list of a user
+pub async fn export(
+    db: &DatabaseConnection,
+    logged_in: &entity::user::Model,
+    format: ExportFormat,
+) -> Result<ReadingExportReply, Status> {
+    let entries = entries(db, logged_in.id).await?;
+
+    let (extension, content_type, data) = match format {
+        ExportFormat::MyAnimeList => ("xml", "application/xml", mal::write(&logged_in.username, &entries)),
+        ExportFormat::Csv => ("csv", "text/csv", csv::write(&entries)),
+        ExportFormat::Tachiyomi => ("tachibk", "application/gzip", tachiyomi::write(&entries)?),
+        ExportFormat::Unknown => return Err(Status::invalid_argument("Export format is missing")),
+    };
+
+    Ok(ReadingExportReply {
+        filename: format!("rumgap_{}.{extension}", logged_in.username),
+        content_type: content_type.to_string(),
+        data,
+    })
+}
@@ -0,0 +1,43 @@
+use std::io::Write;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use prost::Message;
+use tonic::Status;
+
+use super::Entry;
+use crate::util::import::tachiyomi::{Backup, BackupChapter, BackupManga};
+
+/// Write a gzipped Tachiyomi/ Mihon backup
+///
+/// Sources are unknown to rumgap, so the full URLs are used and the source is left empty
+pub fn write(entries: &[Entry]) -> Result<Vec<u8>, Status> {
+    let backup = Backup {
+        backup_manga: entries
+            .iter()
+            .map(|entry| BackupManga {
+                source: 0,
+                url: entry.manga.url.clone(),
+                title: entry.manga.title.clone(),
+                favorite: true,
+                chapters: entry
+                    .chapters
+                    .iter()
+                    .map(|chapter| BackupChapter {
+                        url: chapter.url.clone(),
+                        name: chapter.title.clone(),
+                        read: entry.read.contains(&chapter.id),
+                        last_page_read: entry.offsets.get(&chapter.id).map_or(0, |offset| offset.page as i64),
+                        chapter_number: chapter.number,
+                    })
+                    .collect(),
+            })
+            .collect(),
+    };
+
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder
+        .write_all(&backup.encode_to_vec())
+        .map_err(|e| Status::internal(e.to_string()))?;
+    encoder.finish().map_err(|e| Status::internal(e.to_string()))
+}
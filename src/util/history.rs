@@ -0,0 +1,43 @@
+use sea_orm::ActiveValue::Set;
+use sea_orm::{DatabaseConnection, EntityTrait};
+use tonic::Status;
+
+/// Record chapters that were marked as read or unread
+///
+/// `progress` is the progress after the change, every chapter gets the progress it was at after that chapter
+pub async fn record(
+    db: &DatabaseConnection,
+    user_id: i32,
+    manga_id: i32,
+    chapter_ids: &[i32],
+    read: bool,
+    progress: i32,
+) -> Result<(), Status> {
+    if chapter_ids.is_empty() {
+        return Ok(());
+    }
+
+    let count = chapter_ids.len() as i32;
+    let entries = chapter_ids.iter().enumerate().map(|(index, chapter_id)| {
+        let index = index as i32;
+        entity::reading_history::ActiveModel {
+            user_id: Set(user_id),
+            manga_id: Set(manga_id),
+            chapter_id: Set(Some(*chapter_id)),
+            progress: Set(if read {
+                progress - count + index + 1
+            } else {
+                progress + count - index - 1
+            }),
+            read: Set(read),
+            ..Default::default()
+        }
+    });
+
+    entity::reading_history::Entity::insert_many(entries)
+        .exec_without_returning(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(())
+}
@@ -0,0 +1,356 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use hmac::Mac;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, REFERER};
+use sha2::{Digest, Sha256};
+use tonic::Status;
+
+use crate::interceptor::auth::SECRET_KEY;
+use crate::proto::{ImageCacheReply, ImageRequest};
+use crate::util::scraper_config::{host_key, HOST_CONFIGS};
+
+/// Images bigger than this are not proxied
+const MAX_IMAGE_SIZE: u64 = 20 * 1024 * 1024;
+
+lazy_static! {
+    /// Cache shared by all requests
+    pub static ref IMAGE_CACHE: ImageCache = ImageCache::open(
+        PathBuf::from(std::env::var("IMAGE_CACHE_DIR").unwrap_or("cache/images".to_string())),
+        std::env::var("IMAGE_CACHE_MAX_BYTES")
+            .unwrap_or("1073741824".to_string())
+            .parse()
+            .unwrap_or(1073741824),
+    );
+    static ref CLIENT: reqwest::Client = reqwest::Client::builder()
+        .user_agent(
+            std::env::var("IMAGE_PROXY_USER_AGENT").unwrap_or(
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0 Safari/537.36"
+                    .to_string()
+            )
+        )
+        .timeout(std::time::Duration::from_secs(30))
+        .build()
+        .unwrap();
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn mac(url: &str, referer: &str) -> hmac::Hmac<Sha256> {
+    let mut mac = SECRET_KEY.clone();
+    mac.update(url.as_bytes());
+    mac.update(b"\n");
+    mac.update(referer.as_bytes());
+    mac
+}
+
+/// Reference to an image through the proxy, signed so the proxy can only load images that were handed out
+pub fn sign(url: &str, referer: &str) -> ImageRequest {
+    ImageRequest {
+        url: url.to_string(),
+        referer: referer.to_string(),
+        signature: to_hex(&mac(url, referer).finalize().into_bytes()),
+    }
+}
+
+/// Check that an image reference was made by this server
+pub fn verify(image: &ImageRequest) -> Result<(), Status> {
+    from_hex(&image.signature)
+        .and_then(|signature| mac(&image.url, &image.referer).verify_slice(&signature).ok())
+        .ok_or(Status::permission_denied("Invalid image signature"))
+}
+
+/// Type of an image by its first bytes
+pub fn content_type(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [0x89, b'P', b'N', b'G', ..] => Some("image/png"),
+        [b'G', b'I', b'F', b'8', ..] => Some("image/gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        [_, _, _, _, b'f', b't', b'y', b'p', b'a', b'v', b'i', b'f', ..] => Some("image/avif"),
+        _ => None,
+    }
+}
+
+/// Headers for loading an image, the scraper config of the website or image host can add to them
+fn headers(url: &str, referer: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        ACCEPT,
+        HeaderValue::from_static("image/avif,image/webp,image/*,*/*;q=0.8"),
+    );
+    if let Ok(referer) = HeaderValue::from_str(referer) {
+        headers.insert(REFERER, referer);
+    }
+
+    for key in [host_key(referer), host_key(url)] {
+        let Some(config) = HOST_CONFIGS.get(&key) else {
+            continue;
+        };
+        for (name, value) in &config.image_headers {
+            if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::from_str(value)) {
+                headers.insert(name, value);
+            }
+        }
+    }
+
+    headers
+}
+
+#[derive(Debug, Default)]
+struct Index {
+    /// Size and last use per cached file
+    entries: HashMap<String, (u64, u64)>,
+    /// Cached files from least to most recently used
+    order: BTreeMap<u64, String>,
+    size: u64,
+    tick: u64,
+}
+
+impl Index {
+    /// Mark a file as just used
+    fn touch(&mut self, key: &str) -> bool {
+        self.tick += 1;
+        let tick = self.tick;
+        let Some((_size, used)) = self.entries.get_mut(key) else {
+            return false;
+        };
+        self.order.remove(used);
+        *used = tick;
+        self.order.insert(tick, key.to_string());
+        true
+    }
+
+    fn insert(&mut self, key: String, size: u64) {
+        self.remove(&key);
+        self.tick += 1;
+        self.size += size;
+        self.entries.insert(key.clone(), (size, self.tick));
+        self.order.insert(self.tick, key);
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((size, used)) = self.entries.remove(key) {
+            self.order.remove(&used);
+            self.size -= size;
+        }
+    }
+
+    /// Remove the least recently used files until the cache fits, returns the removed files
+    fn evict(&mut self, max_size: u64) -> Vec<String> {
+        let mut evicted = vec![];
+        while self.size > max_size {
+            let Some((_used, key)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some((size, _used)) = self.entries.remove(&key) {
+                self.size -= size;
+            }
+            evicted.push(key);
+        }
+        evicted
+    }
+}
+
+/// Images on disk, removing the least recently used ones when it grows too big
+pub struct ImageCache {
+    dir: PathBuf,
+    max_size: u64,
+    index: Mutex<Index>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    errors: AtomicU64,
+    evictions: AtomicU64,
+    /// Temporary files written, so every write of an image gets its own file
+    writes: AtomicU64,
+}
+
+impl ImageCache {
+    /// Use a directory as cache, keeping the files that are already in it
+    pub fn open(dir: PathBuf, max_size: u64) -> Self {
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            error!("Could not create image cache {}: {}", dir.display(), e);
+        }
+
+        let mut files: Vec<(SystemTime, String, u64)> = std::fs::read_dir(&dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|entry| {
+                        let metadata = entry.metadata().ok().filter(|metadata| metadata.is_file())?;
+                        let name = entry.file_name().into_string().ok()?;
+                        Some((metadata.modified().ok()?, name, metadata.len()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        files.sort();
+
+        let mut index = Index::default();
+        for (_modified, name, size) in files {
+            if name.ends_with(".tmp") {
+                let _ = std::fs::remove_file(dir.join(name));
+            } else {
+                index.insert(name, size);
+            }
+        }
+
+        let cache = Self {
+            dir,
+            max_size,
+            index: Mutex::new(index),
+            hits: AtomicU64::default(),
+            misses: AtomicU64::default(),
+            errors: AtomicU64::default(),
+            evictions: AtomicU64::default(),
+            writes: AtomicU64::default(),
+        };
+        cache.evict();
+        cache
+    }
+
+    fn evict(&self) {
+        let evicted = self.index.lock().unwrap().evict(self.max_size);
+        self.evictions.fetch_add(evicted.len() as u64, Ordering::Relaxed);
+        for key in evicted {
+            let _ = std::fs::remove_file(self.dir.join(key));
+        }
+    }
+
+    /// Get an image from the cache, or load it from its website
+    ///
+    /// The referer is the page the image is shown on
+    pub async fn get(&self, url: &str, referer: &str) -> Result<(Vec<u8>, &'static str), Status> {
+        let key = to_hex(&Sha256::digest(url.as_bytes()));
+        let path = self.dir.join(&key);
+
+        if self.index.lock().unwrap().touch(&key) {
+            match tokio::fs::read(&path).await {
+                Ok(bytes) => {
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    let content_type = content_type(&bytes).unwrap_or("application/octet-stream");
+                    return Ok((bytes, content_type));
+                }
+                Err(e) => {
+                    warn!("Could not read cached image {}: {}", path.display(), e);
+                    self.index.lock().unwrap().remove(&key);
+                }
+            }
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let bytes = match fetch(url, referer).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+                return Err(e);
+            }
+        };
+        let content_type = content_type(&bytes).ok_or_else(|| {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+            Status::failed_precondition("Source did not return an image")
+        })?;
+
+        // Write to a temporary file first, so a half written image is never served,
+        // the same image can be loaded by two requests at once so each gets its own file
+        let write = self.writes.fetch_add(1, Ordering::Relaxed);
+        let tmp = self.dir.join(format!("{key}.{write}.tmp"));
+        let written = match tokio::fs::write(&tmp, &bytes).await {
+            Ok(()) => tokio::fs::rename(&tmp, &path).await,
+            Err(e) => Err(e),
+        };
+        match written {
+            Ok(()) => {
+                self.index.lock().unwrap().insert(key, bytes.len() as u64);
+                self.evict();
+            }
+            Err(e) => warn!("Could not cache image {}: {}", url, e),
+        }
+
+        Ok((bytes, content_type))
+    }
+
+    pub fn stats(&self) -> ImageCacheReply {
+        let index = self.index.lock().unwrap();
+        ImageCacheReply {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            entries: index.entries.len() as u64,
+            size: index.size,
+            max_size: self.max_size,
+        }
+    }
+}
+
+/// Load an image from its website
+async fn fetch(url: &str, referer: &str) -> Result<Vec<u8>, Status> {
+    let mut response = CLIENT
+        .get(url)
+        .headers(headers(url, referer))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| Status::unavailable(format!("Could not load image: {e}")))?;
+
+    if response.content_length().is_some_and(|size| size > MAX_IMAGE_SIZE) {
+        return Err(Status::failed_precondition("Image is too big"));
+    }
+
+    // The content length is not always known, so stop reading as soon as the image is too big
+    let mut bytes = vec![];
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| Status::unavailable(format!("Could not load image: {e}")))?
+    {
+        if (bytes.len() + chunk.len()) as u64 > MAX_IMAGE_SIZE {
+            return Err(Status::failed_precondition("Image is too big"));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(bytes)
+}
+
+#[test]
+fn evict_least_recently_used() {
+    let mut index = Index::default();
+    index.insert("a".to_string(), 10);
+    index.insert("b".to_string(), 10);
+    index.insert("c".to_string(), 10);
+    assert!(index.touch("a"));
+
+    assert_eq!(index.evict(20), vec!["b".to_string()]);
+    assert_eq!(index.size, 20);
+    assert!(!index.touch("b"));
+    assert_eq!(index.evict(0), vec!["c".to_string(), "a".to_string()]);
+}
+
+#[test]
+fn signed_images() {
+    let image = sign("https://cdn.example.com/1.jpg", "https://example.com/chapter-1");
+    assert!(verify(&image).is_ok());
+
+    let forged = ImageRequest {
+        url: "https://evil.example.com/1.jpg".to_string(),
+        ..image
+    };
+    assert!(verify(&forged).is_err());
+}
@@ -0,0 +1,83 @@
+use serde::Deserialize;
+use serde_json::Value;
+use tonic::Status;
+
+use super::Entry;
+
+#[derive(Deserialize)]
+struct List {
+    entries: Vec<ListEntry>,
+}
+
+#[derive(Deserialize)]
+struct ListEntry {
+    progress: Option<i32>,
+    media: Media,
+}
+
+#[derive(Deserialize)]
+struct Media {
+    #[serde(rename = "type")]
+    media_type: Option<String>,
+    title: Title,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Title {
+    english: Option<String>,
+    romaji: Option<String>,
+    native: Option<String>,
+    user_preferred: Option<String>,
+}
+
+/// Parse an AniList JSON export
+///
+/// This is the `MediaListCollection` from the AniList API, with or without the `data` around it
+pub fn parse(data: &[u8]) -> Result<Vec<Entry>, Status> {
+    let json: Value = serde_json::from_slice(data).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+    let lists = [
+        "/data/MediaListCollection/lists",
+        "/MediaListCollection/lists",
+        "/lists",
+    ]
+    .iter()
+    .find_map(|pointer| json.pointer(pointer))
+    .ok_or(Status::invalid_argument("Not an AniList export"))?;
+
+    let lists: Vec<List> =
+        serde_json::from_value(lists.clone()).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+    Ok(lists
+        .into_iter()
+        .flat_map(|list| list.entries)
+        .filter(|entry| {
+            entry
+                .media
+                .media_type
+                .as_deref()
+                .is_none_or(|media_type| media_type == "MANGA")
+        })
+        .filter_map(|entry| {
+            let Title {
+                english,
+                romaji,
+                native,
+                user_preferred,
+            } = entry.media.title;
+            let mut titles: Vec<String> = [user_preferred, english, romaji, native]
+                .into_iter()
+                .flatten()
+                .collect();
+            titles.dedup();
+
+            let title = titles.first()?.clone();
+            Some(Entry {
+                title,
+                alternative_titles: titles.split_off(1),
+                progress: entry.progress.unwrap_or(0),
+            })
+        })
+        .collect())
+}
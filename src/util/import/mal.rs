@@ -0,0 +1,86 @@
+use regex::Regex;
+use tonic::Status;
+
+use super::Entry;
+
+lazy_static! {
+    static ref MANGA: Regex = Regex::new(r"(?s)<manga>(.*?)</manga>").unwrap();
+    static ref TITLE: Regex = Regex::new(r"(?s)<manga_title>(.*?)</manga_title>").unwrap();
+    static ref READ_CHAPTERS: Regex = Regex::new(r"<my_read_chapters>\s*(\d+)\s*</my_read_chapters>").unwrap();
+    static ref CDATA: Regex = Regex::new(r"(?s)^\s*<!\[CDATA\[(.*)\]\]>\s*$").unwrap();
+}
+
+/// Get the text of an element, unwrapping CDATA and unescaping entities
+fn text(value: &str) -> String {
+    match CDATA.captures(value) {
+        Some(captures) => captures[1].trim().to_string(),
+        None => value
+            .trim()
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    }
+}
+
+/// Parse a MyAnimeList XML export
+pub fn parse(data: &[u8]) -> Result<Vec<Entry>, Status> {
+    let xml = std::str::from_utf8(data).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+    if !xml.contains("<myanimelist>") {
+        return Err(Status::invalid_argument("Not a MyAnimeList export"));
+    }
+
+    Ok(MANGA
+        .captures_iter(xml)
+        .filter_map(|manga| {
+            let manga = &manga[1];
+            let title = text(&TITLE.captures(manga)?[1]);
+            let progress = READ_CHAPTERS
+                .captures(manga)
+                .and_then(|captures| captures[1].parse().ok())
+                .unwrap_or(0);
+
+            Some(Entry {
+                title,
+                alternative_titles: vec![],
+                progress,
+            })
+        })
+        .collect())
+}
+
+#[test]
+fn parse_mal_export() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<myanimelist>
+    <myinfo><user_export_type>2</user_export_type></myinfo>
+    <manga>
+        <manga_mangadb_id>2</manga_mangadb_id>
+        <manga_title><![CDATA[Berserk]]></manga_title>
+        <my_read_chapters>42</my_read_chapters>
+    </manga>
+    <manga>
+        <manga_title>Kaguya-sama &amp; Friends</manga_title>
+        <my_read_chapters>0</my_read_chapters>
+    </manga>
+</myanimelist>"#;
+
+    let entries = parse(xml.as_bytes()).unwrap();
+    assert_eq!(
+        entries,
+        vec![
+            Entry {
+                title: String::from("Berserk"),
+                alternative_titles: vec![],
+                progress: 42,
+            },
+            Entry {
+                title: String::from("Kaguya-sama & Friends"),
+                alternative_titles: vec![],
+                progress: 0,
+            },
+        ]
+    );
+}
@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use manga_parser::Url;
+use migration::{Expr, Func, OnConflict};
+use sea_orm::ActiveValue::Set;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use tokio::time::timeout;
+use tonic::Status;
+
+use crate::proto::{ImportFormat, ImportStatus, ReadingImportReply};
+use crate::service::v1::manga::{get_manga_by_id, save_manga};
+use crate::util::chapter_read;
+use crate::util::scrape_error_proto::StatusWrapper;
+use crate::MANGA_PARSER;
+
+pub mod anilist;
+pub mod mal;
+pub mod tachiyomi;
+
+/// A manga from an imported reading list
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub title: String,
+    /// Other titles that can be used to find the manga
+    pub alternative_titles: Vec<String>,
+    /// Amount of chapters read
+    pub progress: i32,
+}
+
+impl Entry {
+    fn titles(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.title).chain(self.alternative_titles.iter())
+    }
+}
+
+/// Parse an exported reading list
+pub fn parse(format: ImportFormat, data: &[u8]) -> Result<Vec<Entry>, Status> {
+    match format {
+        ImportFormat::MyAnimeList => mal::parse(data),
+        ImportFormat::AniList => anilist::parse(data),
+        ImportFormat::Tachiyomi => tachiyomi::parse(data),
+        ImportFormat::Unknown => Err(Status::invalid_argument("Import format is missing")),
+    }
+}
+
+/// Find the ID of the manga belonging to an entry
+///
+/// Looks for a manga with the same title first, otherwise the title is searched for online
+///
+/// Only a result with the same title is used, so the progress does not end up on an unrelated manga
+async fn find_manga(
+    db: &DatabaseConnection,
+    logged_in: &entity::user::Model,
+    entry: &Entry,
+) -> Result<Option<i32>, Status> {
+    let titles: Vec<String> = entry.titles().map(|title| title.to_lowercase()).collect();
+
+    let existing = entity::manga::Entity::find()
+        .filter(Expr::expr(Func::lower(Expr::col(entity::manga::Column::Title))).is_in(titles.clone()))
+        .one(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    if let Some(manga) = existing {
+        return Ok(Some(manga.id));
+    }
+
+    for title in entry.titles() {
+        let results = timeout(
+            Duration::from_secs(10),
+            MANGA_PARSER.search(title, logged_in.preferred_hostnames.as_slice()),
+        )
+        .await
+        .map_err(|e| Status::deadline_exceeded(e.to_string()))?
+        .map_err(StatusWrapper::from)?;
+
+        let matching = results
+            .iter()
+            .find(|result| titles.contains(&result.title.to_lowercase()));
+
+        if let Some(result) = matching {
+            return Ok(Some(manga_by_url(db, logged_in, &result.url).await?));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Get the ID of a manga by URL, scrapes it if it is not saved yet
+async fn manga_by_url(db: &DatabaseConnection, logged_in: &entity::user::Model, url: &Url) -> Result<i32, Status> {
+    let existing = entity::manga::Entity::find()
+        .filter(entity::manga::Column::Url.eq(url.to_string()))
+        .one(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    match existing {
+        Some(manga) => Ok(manga.id),
+        None => Ok(save_manga(db, Some(logged_in), None, url.clone()).await?.id),
+    }
+}
+
+/// Add a manga to the reading list, keeping the highest progress if it is already there
+///
+/// Imported progress is not added to the reading history, the chapters were not read just now
+async fn save_reading(db: &DatabaseConnection, user_id: i32, manga_id: i32, progress: i32) -> Result<(), Status> {
+    use entity::reading::Column;
+
+    entity::reading::Entity::insert(entity::reading::ActiveModel {
+        user_id: Set(user_id),
+        manga_id: Set(manga_id),
+        progress: Set(progress),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::columns([Column::UserId, Column::MangaId])
+            .value(
+                Column::Progress,
+                Expr::cust("GREATEST(reading.progress, EXCLUDED.progress)"),
+            )
+            .to_owned(),
+    )
+    .exec(db)
+    .await
+    .map_err(|e| Status::internal(e.to_string()))?;
+
+    // Chapters that were already read stay read
+    let chapter_ids = chapter_read::chapter_ids(db, manga_id).await?;
+    let read = &chapter_ids[..(progress.max(0) as usize).min(chapter_ids.len())];
+    chapter_read::mark(db, user_id, manga_id, read, true).await?;
+
+    Ok(())
+}
+
+/// Import a single entry into the reading list of a user
+pub async fn import_entry(
+    db: &DatabaseConnection,
+    logged_in: &entity::user::Model,
+    entry: Entry,
+) -> ReadingImportReply {
+    let result = async {
+        let Some(manga_id) = find_manga(db, logged_in, &entry).await? else {
+            return Ok(None);
+        };
+        save_reading(db, logged_in.id, manga_id, entry.progress).await?;
+
+        get_manga_by_id(db, Some(logged_in), manga_id).await.map(Some)
+    }
+    .await;
+
+    let (status, manga, message) = match result {
+        Ok(Some(manga)) => (ImportStatus::Imported, Some(manga), None),
+        Ok(None) => (
+            ImportStatus::Unmatched,
+            None,
+            Some(String::from("No manga found with this title")),
+        ),
+        Err(e) => {
+            warn!("Failed to import '{}': {}", entry.title, e.message());
+            (ImportStatus::ImportFailed, None, Some(e.message().to_string()))
+        }
+    };
+
+    ReadingImportReply {
+        title: entry.title,
+        progress: entry.progress,
+        status: status.into(),
+        manga,
+        message,
+    }
+}
@@ -0,0 +1,99 @@
+use std::io::Read;
+
+use flate2::read::GzDecoder;
+use prost::Message;
+use tonic::Status;
+
+use super::Entry;
+
+/// Largest backup after decompressing, so a small upload can not fill the memory
+const MAX_BACKUP_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Tachiyomi (and Mihon) backup, only the fields that are needed
+#[derive(Clone, PartialEq, Message)]
+pub struct Backup {
+    #[prost(message, repeated, tag = "1")]
+    pub backup_manga: Vec<BackupManga>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BackupManga {
+    #[prost(int64, tag = "1")]
+    pub source: i64,
+    #[prost(string, tag = "2")]
+    pub url: String,
+    #[prost(string, tag = "3")]
+    pub title: String,
+    #[prost(message, repeated, tag = "16")]
+    pub chapters: Vec<BackupChapter>,
+    #[prost(bool, tag = "100")]
+    pub favorite: bool,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BackupChapter {
+    #[prost(string, tag = "1")]
+    pub url: String,
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(bool, tag = "4")]
+    pub read: bool,
+    #[prost(int64, tag = "6")]
+    pub last_page_read: i64,
+    #[prost(float, tag = "9")]
+    pub chapter_number: f32,
+}
+
+/// Decompress gzipped data, failing when it is larger than `limit`
+fn gunzip(data: &[u8], limit: u64) -> Result<Vec<u8>, Status> {
+    let mut decompressed = vec![];
+    GzDecoder::new(data)
+        .take(limit + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+    if decompressed.len() as u64 > limit {
+        return Err(Status::invalid_argument(format!(
+            "Backup is larger than {limit} bytes after decompressing"
+        )));
+    }
+
+    Ok(decompressed)
+}
+
+/// Parse a Tachiyomi/ Mihon backup (.tachibk or .proto.gz)
+pub fn parse(data: &[u8]) -> Result<Vec<Entry>, Status> {
+    // Backups are gzipped, but accept plain protobuf too
+    let decompressed;
+    let data = if data.starts_with(&[0x1f, 0x8b]) {
+        decompressed = gunzip(data, MAX_BACKUP_BYTES)?;
+        decompressed.as_slice()
+    } else {
+        data
+    };
+
+    let backup = Backup::decode(data).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+    Ok(backup
+        .backup_manga
+        .into_iter()
+        .filter(|manga| !manga.title.is_empty())
+        .map(|manga| Entry {
+            progress: manga.chapters.iter().filter(|chapter| chapter.read).count() as i32,
+            title: manga.title,
+            alternative_titles: vec![],
+        })
+        .collect())
+}
+
+#[test]
+fn gunzip_limit() {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(&[0; 1024]).unwrap();
+    let data = encoder.finish().unwrap();
+
+    assert_eq!(gunzip(&data, 1024).unwrap().len(), 1024);
+    assert_eq!(gunzip(&data, 1023).unwrap_err().code(), tonic::Code::InvalidArgument);
+}
@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+use lettre::message::Mailbox;
+use lettre::{AsyncFileTransport, AsyncTransport, Tokio1Executor};
+
+use super::{Mail, MailError, Mailer};
+
+/// Mailer that writes every mail as an .eml file to a directory
+pub struct FileMailer {
+    transport: AsyncFileTransport<Tokio1Executor>,
+    dir: PathBuf,
+    from: Mailbox,
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>, from: Mailbox) -> Self {
+        let dir = dir.into();
+        Self {
+            transport: AsyncFileTransport::new(&dir),
+            dir,
+            from,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let id = self.transport.send(mail.message(&self.from)?).await?;
+        debug!("[Mail] Written mail {} to {}", id, self.dir.display());
+        Ok(())
+    }
+}
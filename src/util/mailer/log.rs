@@ -0,0 +1,15 @@
+use super::{Mail, MailError, Mailer};
+
+/// Mailer that only writes mail to the log
+///
+/// The body can hold reset and verification tokens, so it is only logged at debug level
+pub struct LogMailer;
+
+#[tonic::async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        info!("[Mail] To: {} | Subject: {}", mail.to, mail.subject);
+        debug!("[Mail] Body:\n{}", mail.body);
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+use std::error::Error;
+
+use lettre::message::Mailbox;
+
+pub mod file;
+pub mod log;
+pub mod smtp;
+
+pub type MailError = Box<dyn Error + Send + Sync>;
+
+lazy_static! {
+    /// Mailer picked with the "MAILER" env var, or why its settings are invalid
+    static ref MAILER: Result<Box<dyn Mailer>, String> = from_env().map_err(|e| e.to_string());
+}
+
+/// A plain text mail
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+impl Mail {
+    /// Build a lettre message from this mail
+    fn message(&self, from: &Mailbox) -> Result<lettre::Message, MailError> {
+        Ok(lettre::Message::builder()
+            .from(from.clone())
+            .to(self.to.parse()?)
+            .subject(&self.subject)
+            .body(self.body.clone())?)
+    }
+}
+
+/// Something that can deliver mail
+#[tonic::async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: Mail) -> Result<(), MailError>;
+}
+
+/// Create the mailer configured in the environment
+///
+/// MAILER=log (default) logs all mail
+/// MAILER=file writes all mail to MAILER_DIR
+/// MAILER=smtp sends all mail to SMTP_URL
+///
+/// Any other value is an error, so a typo does not silently log mail with tokens in it
+fn from_env() -> Result<Box<dyn Mailer>, MailError> {
+    let from: Mailbox = std::env::var("MAIL_FROM")
+        .unwrap_or("RumGap <noreply@localhost>".to_string())
+        .parse()
+        .map_err(|e| format!("MAIL_FROM is not a valid mailbox: {e}"))?;
+
+    Ok(match std::env::var("MAILER").unwrap_or_default().as_str() {
+        "file" => {
+            let dir = std::env::var("MAILER_DIR").unwrap_or("mail".to_string());
+            Box::new(file::FileMailer::new(dir, from))
+        }
+        "smtp" => {
+            let url = std::env::var("SMTP_URL").map_err(|_| "SMTP_URL is not set in .env file")?;
+            Box::new(smtp::SmtpMailer::new(&url, from).map_err(|e| format!("SMTP_URL is invalid: {e}"))?)
+        }
+        "" | "log" => Box::new(log::LogMailer),
+        mailer => return Err(format!("MAILER should be log, file or smtp, not '{mailer}'").into()),
+    })
+}
+
+/// Check the mailer settings, so invalid ones stop the server at startup instead of failing the first mail
+pub fn check() -> Result<(), String> {
+    MAILER.as_ref().map(|_| ()).map_err(|e| e.clone())
+}
+
+/// Send a mail with the configured mailer
+pub async fn send(mail: Mail) -> Result<(), MailError> {
+    MAILER.as_ref().map_err(|e| e.clone())?.send(mail).await
+}
+
+/// Send a mail in the background, failures are only logged
+pub fn send_in_background(mail: Mail) {
+    tokio::spawn(async move {
+        let to = mail.to.clone();
+        if let Err(e) = send(mail).await {
+            error!("Failed to send mail to {}: {}", to, e);
+        }
+    });
+}
@@ -1,8 +1,29 @@
+pub mod account_token;
 pub mod argon;
+pub mod audit;
 pub mod auth;
+pub mod auth_error_proto;
+pub mod bus;
+pub mod cbz;
+pub mod chapter_diff;
+pub mod chapter_image;
+pub mod chapter_read;
 pub mod db;
+pub mod export;
+pub mod history;
+pub mod image_proxy;
+pub mod import;
+pub mod mailer;
+pub mod notifier;
 pub mod order;
+pub mod polite_scraper;
+pub mod rate_limit;
 pub mod scrape_error_proto;
+pub mod scrape_failure;
+pub mod scraper_config;
 pub mod search;
+pub mod session;
+pub mod token;
+pub mod update_job;
 pub mod updater;
 pub mod verify;
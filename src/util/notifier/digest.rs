@@ -0,0 +1,170 @@
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use migration::Expr;
+use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder};
+use tokio::time::{self, Duration};
+
+use super::{default_settings, notify, settings_of, NewChapters, Notice};
+
+/// Whether a user does not want to be disturbed at the given moment
+pub fn in_quiet_hours(settings: &entity::notification_setting::Model, now: DateTime<Utc>) -> bool {
+    let (Some(start), Some(end)) = (settings.quiet_start, settings.quiet_end) else {
+        return false;
+    };
+
+    let timezone: Tz = settings.timezone.parse().unwrap_or(Tz::UTC);
+    let time = now.with_timezone(&timezone).time();
+
+    if start <= end {
+        start <= time && time < end
+    } else {
+        // Quiet hours over midnight, like 22:00 - 07:00
+        time >= start || time < end
+    }
+}
+
+/// One notification for all new chapters, grouped per manga
+pub fn digest(updates: &[NewChapters]) -> Notice {
+    let mut grouped: Vec<NewChapters> = vec![];
+    for update in updates {
+        match grouped.iter_mut().find(|group| group.manga_id == update.manga_id) {
+            Some(group) => group.chapter_ids.extend(&update.chapter_ids),
+            None => grouped.push(update.clone()),
+        }
+    }
+
+    if let [update] = grouped.as_slice() {
+        return update.notice();
+    }
+
+    Notice {
+        manga_id: None,
+        chapter_ids: grouped.iter().flat_map(|group| group.chapter_ids.clone()).collect(),
+        title: format!("{} Manga Updated!", grouped.len()),
+        body: grouped
+            .iter()
+            .map(|group| group.manga_title.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
+
+/// Send the notifications that were held back during quiet hours, as one digest per user
+///
+/// Returns the amount of digests that were sent
+pub async fn send_digests(db: &DatabaseConnection) -> Result<usize, DbErr> {
+    let pending = entity::notification::Entity::find()
+        .filter(entity::notification::Column::Pending.eq(true))
+        .order_by_asc(entity::notification::Column::Id)
+        .find_also_related(entity::manga::Entity)
+        .all(db)
+        .await?;
+
+    if pending.is_empty() {
+        return Ok(0);
+    }
+
+    let user_ids: BTreeSet<i32> = pending.iter().map(|(notification, _)| notification.user_id).collect();
+    let users = entity::user::Entity::find()
+        .filter(entity::user::Column::Id.is_in(user_ids.iter().copied()))
+        .all(db)
+        .await?;
+    let mut settings = settings_of(db, user_ids).await?;
+
+    let now = Utc::now();
+    let mut sent = 0;
+    for user in users {
+        let settings = settings.remove(&user.id).unwrap_or_else(|| default_settings(user.id));
+        if in_quiet_hours(&settings, now) {
+            continue;
+        }
+
+        let (ids, updates): (Vec<i32>, Vec<NewChapters>) = pending
+            .iter()
+            .filter(|(notification, _)| notification.user_id == user.id)
+            .map(|(notification, manga)| {
+                (
+                    notification.id,
+                    NewChapters {
+                        manga_id: notification.manga_id,
+                        manga_title: manga.as_ref().map(|manga| manga.title.clone()).unwrap_or_default(),
+                        chapter_ids: notification.chapter_ids.clone(),
+                    },
+                )
+            })
+            .unzip();
+
+        // Marked before sending, so a digest is never sent twice
+        entity::notification::Entity::update_many()
+            .col_expr(entity::notification::Column::Pending, Expr::value(false))
+            .filter(entity::notification::Column::Id.is_in(ids))
+            .exec(db)
+            .await?;
+
+        notify(db, &user, &settings, &digest(&updates)).await;
+        sent += 1;
+    }
+
+    Ok(sent)
+}
+
+/// Keep sending digests to users whose quiet hours ended
+pub async fn watch_digests(db: &DatabaseConnection) {
+    let interval_ms: u64 = std::env::var("NOTIFY_DIGEST_INTERVAL_MS")
+        .unwrap_or("60000".to_string())
+        .parse()
+        .unwrap_or(60000);
+
+    let mut interval = time::interval(Duration::from_millis(interval_ms));
+
+    loop {
+        interval.tick().await;
+
+        match send_digests(db).await {
+            Ok(0) => {}
+            Ok(sent) => info!("[Notify] Sent {} digests", sent),
+            Err(e) => error!("[Notify] Failed to send digests: {}", e),
+        }
+    }
+}
+
+#[test]
+fn quiet_hours_over_midnight() {
+    use chrono::{NaiveTime, TimeZone};
+
+    let settings = entity::notification_setting::Model {
+        quiet_start: NaiveTime::from_hms_opt(22, 0, 0),
+        quiet_end: NaiveTime::from_hms_opt(7, 0, 0),
+        timezone: "Europe/Amsterdam".to_string(),
+        ..default_settings(1)
+    };
+    // Amsterdam is UTC+2 in the summer
+    let at = |hour: u32| Utc.with_ymd_and_hms(2026, 7, 1, hour, 30, 0).unwrap();
+
+    assert!(in_quiet_hours(&settings, at(20)));
+    assert!(in_quiet_hours(&settings, at(4)));
+    assert!(!in_quiet_hours(&settings, at(5)));
+    assert!(!in_quiet_hours(&settings, at(12)));
+    assert!(!in_quiet_hours(&default_settings(1), at(20)));
+}
+
+#[test]
+fn digest_groups_per_manga() {
+    let update = |manga_id: i32, manga_title: &str, chapter_id: i32| NewChapters {
+        manga_id,
+        manga_title: manga_title.to_string(),
+        chapter_ids: vec![chapter_id],
+    };
+
+    let single = digest(&[update(1, "One", 10), update(1, "One", 11)]);
+    assert_eq!(single.manga_id, Some(1));
+    assert_eq!(single.chapter_ids, vec![10, 11]);
+
+    let several = digest(&[update(1, "One", 10), update(2, "Two", 20), update(1, "One", 11)]);
+    assert_eq!(several.manga_id, None);
+    assert_eq!(several.title, "2 Manga Updated!");
+    assert_eq!(several.body, "One, Two");
+    assert_eq!(several.chapter_ids, vec![10, 11, 20]);
+}
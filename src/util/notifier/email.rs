@@ -0,0 +1,29 @@
+use super::{Notice, Notifier, NotifyError};
+use crate::util::mailer::{self, Mail};
+
+/// Mails notifications to the (verified) address of the user
+pub struct EmailNotifier {
+    to: String,
+}
+
+impl EmailNotifier {
+    pub fn new(to: String) -> Self {
+        Self { to }
+    }
+}
+
+#[tonic::async_trait]
+impl Notifier for EmailNotifier {
+    fn channel(&self) -> &'static str {
+        "email"
+    }
+
+    async fn send(&self, notice: &Notice) -> Result<(), NotifyError> {
+        mailer::send(Mail {
+            to: self.to.clone(),
+            subject: notice.title.clone(),
+            body: notice.body.clone(),
+        })
+        .await
+    }
+}
@@ -0,0 +1,262 @@
+use fcm::message::{Message, Notification, Target};
+use fcm::FcmClient;
+use migration::Expr;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, DbErr, EntityTrait, QueryFilter, Statement,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::{OnceCell, Semaphore};
+
+use super::{InvalidTarget, Notice, Notifier, NotifyError};
+
+lazy_static! {
+    /// Service account key of the Firebase project
+    static ref KEY_PATH: String = std::env::var("FCM_SERVICE_ACCOUNT_KEY_PATH")
+        .unwrap_or("manga-reader-5c535-148af5dd8096.json".to_string());
+    /// Messages that are sent to FCM at the same time
+    static ref SENDS: Semaphore = Semaphore::new(
+        std::env::var("FCM_CONCURRENCY")
+            .unwrap_or("16".to_string())
+            .parse()
+            .unwrap_or(16),
+    );
+}
+
+/// Built on first use and kept when it worked, so a missing key is tried again later
+static CLIENT: OnceCell<FcmClient> = OnceCell::const_new();
+
+/// Add a delivery to the stats of a token, $3 and $4 are the amount of sent and failed messages
+const RECORD_QUERY: &str = r#"
+INSERT INTO device_token_stat (user_id, token, sent, failed, last_sent_at, last_failed_at, last_error)
+VALUES ($1, $2, $3, $4, CASE WHEN $3 > 0 THEN NOW() END, CASE WHEN $4 > 0 THEN NOW() END, $5)
+ON CONFLICT (user_id, token) DO UPDATE SET
+    sent = device_token_stat.sent + EXCLUDED.sent,
+    failed = device_token_stat.failed + EXCLUDED.failed,
+    last_sent_at = COALESCE(EXCLUDED.last_sent_at, device_token_stat.last_sent_at),
+    last_failed_at = COALESCE(EXCLUDED.last_failed_at, device_token_stat.last_failed_at),
+    last_error = COALESCE(EXCLUDED.last_error, device_token_stat.last_error),
+    updated_at = NOW()"#;
+
+async fn client() -> Result<&'static FcmClient, NotifyError> {
+    Ok(CLIENT
+        .get_or_try_init(|| {
+            FcmClient::builder()
+                .service_account_key_json_path(KEY_PATH.as_str())
+                .build()
+        })
+        .await?)
+}
+
+/// Error body of the FCM v1 API
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: ErrorBody,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ErrorBody {
+    message: String,
+    status: String,
+    details: Vec<ErrorDetail>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct ErrorDetail {
+    error_code: Option<String>,
+    field_violations: Vec<FieldViolation>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct FieldViolation {
+    field: String,
+}
+
+/// Find the error body in the text of an FCM response or error
+fn error_body(text: &str) -> Option<ErrorBody> {
+    let json = text.get(text.find('{')?..=text.rfind('}')?)?;
+    serde_json::from_str::<ErrorResponse>(json)
+        .ok()
+        .map(|response| response.error)
+}
+
+impl ErrorBody {
+    /// Whether the token will never work again
+    ///
+    /// NOT_FOUND alone also happens for a wrong project or sender, so only UNREGISTERED counts,
+    /// or an invalid argument that is about the token
+    fn is_invalid_token(&self) -> bool {
+        let error_codes = || self.details.iter().filter_map(|detail| detail.error_code.as_deref());
+        if error_codes().any(|code| code == "UNREGISTERED") {
+            return true;
+        }
+
+        let invalid_argument =
+            self.status == "INVALID_ARGUMENT" || error_codes().any(|code| code == "INVALID_ARGUMENT");
+        let names_token = self.message.contains("registration token")
+            || self
+                .details
+                .iter()
+                .flat_map(|detail| &detail.field_violations)
+                .any(|violation| violation.field == "message.token");
+        invalid_argument && names_token
+    }
+}
+
+/// Failed delivery of a message
+#[derive(Debug)]
+struct DeliveryError {
+    message: String,
+    invalid_token: bool,
+}
+
+impl DeliveryError {
+    fn new(message: String) -> Self {
+        let invalid_token = error_body(&message).is_some_and(|body| body.is_invalid_token());
+        Self { message, invalid_token }
+    }
+}
+
+/// Remove a token that does not work anymore from a user
+async fn prune(db: &DatabaseConnection, user_id: i32, token: &str) -> Result<(), DbErr> {
+    entity::user::Entity::update_many()
+        .col_expr(
+            entity::user::Column::DeviceIds,
+            Expr::cust_with_values("array_remove(device_ids, $1)", [token]),
+        )
+        .filter(entity::user::Column::Id.eq(user_id))
+        .exec(db)
+        .await?;
+
+    entity::device_token_stat::Entity::delete_many()
+        .filter(entity::device_token_stat::Column::UserId.eq(user_id))
+        .filter(entity::device_token_stat::Column::Token.eq(token))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Push notification to one device through Firebase Cloud Messaging
+pub struct FcmNotifier {
+    db: DatabaseConnection,
+    user_id: i32,
+    token: String,
+}
+
+impl FcmNotifier {
+    pub fn new(db: DatabaseConnection, user_id: i32, token: String) -> Self {
+        Self { db, user_id, token }
+    }
+
+    async fn record(&self, error: Option<&str>) {
+        let (sent, failed) = if error.is_some() { (0, 1) } else { (1, 0) };
+        let res = self
+            .db
+            .execute(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                RECORD_QUERY,
+                [
+                    self.user_id.into(),
+                    self.token.clone().into(),
+                    sent.into(),
+                    failed.into(),
+                    error.map(str::to_string).into(),
+                ],
+            ))
+            .await;
+
+        if let Err(e) = res {
+            warn!("[Notify] Failed to record FCM delivery of user {}: {}", self.user_id, e);
+        }
+    }
+
+    async fn deliver(&self, message: Message) -> Result<(), DeliveryError> {
+        let _permit = SENDS.acquire().await.expect("the FCM semaphore is never closed");
+
+        let client = client().await.map_err(|e| DeliveryError::new(e.to_string()))?;
+        match client.send(message).await {
+            // Depending on the error, FCM answers with an error status or a response that holds the error,
+            // any error in a response is a failed delivery
+            Ok(response) => {
+                let response = format!("{response:?}");
+                if error_body(&response).is_some() {
+                    Err(DeliveryError::new(response))
+                } else {
+                    debug!("[Notify] FCM response: {}", response);
+                    Ok(())
+                }
+            }
+            Err(e) => Err(DeliveryError::new(format!("{e}: {e:?}"))),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Notifier for FcmNotifier {
+    fn channel(&self) -> &'static str {
+        "fcm"
+    }
+
+    async fn send(&self, notice: &Notice) -> Result<(), NotifyError> {
+        let message = Message {
+            data: notice.manga_id.map(|manga_id| json!({ "manga_id": manga_id })),
+            notification: Some(Notification {
+                title: Some(notice.title.clone()),
+                body: Some(notice.body.clone()),
+                image: None,
+            }),
+            target: Target::Token(self.token.clone()),
+            android: None,
+            webpush: None,
+            apns: None,
+            fcm_options: None,
+        };
+
+        let Err(error) = self.deliver(message).await else {
+            self.record(None).await;
+            return Ok(());
+        };
+
+        if error.invalid_token {
+            info!("[Notify] Removing invalid FCM token of user {}", self.user_id);
+            prune(&self.db, self.user_id, &self.token).await?;
+            return Err(Box::new(InvalidTarget(error.message)));
+        }
+
+        self.record(Some(&error.message)).await;
+        Err(error.message.into())
+    }
+}
+
+#[test]
+fn invalid_tokens() {
+    let invalid = |text: &str| error_body(text).is_some_and(|body| body.is_invalid_token());
+
+    assert!(invalid(
+        r#"{"error": {"code": 404, "message": "Requested entity was not found.", "status": "NOT_FOUND", "details": [{"@type": "type.googleapis.com/google.firebase.fcm.v1.FcmError", "errorCode": "UNREGISTERED"}]}}"#
+    ));
+    assert!(invalid(
+        r#"Response: {"error": {"code": 400, "message": "The registration token is not a valid FCM registration token", "status": "INVALID_ARGUMENT"}}"#
+    ));
+    assert!(invalid(
+        r#"{"error": {"code": 400, "message": "Invalid value", "status": "INVALID_ARGUMENT", "details": [{"fieldViolations": [{"field": "message.token"}]}]}}"#
+    ));
+
+    // A wrong project or sender is not the fault of the token
+    assert!(!invalid(
+        r#"{"error": {"code": 404, "message": "Requested entity was not found.", "status": "NOT_FOUND"}}"#
+    ));
+    assert!(!invalid(
+        r#"{"error": {"code": 400, "message": "Invalid JSON payload", "status": "INVALID_ARGUMENT", "details": [{"fieldViolations": [{"field": "message.data"}]}]}}"#
+    ));
+    assert!(!invalid(r#"{"error": {"code": 503, "status": "UNAVAILABLE"}}"#));
+    assert!(!invalid(
+        r#"{"error": {"code": 429, "status": "RESOURCE_EXHAUSTED", "details": [{"errorCode": "QUOTA_EXCEEDED"}]}}"#
+    ));
+    assert!(!invalid("The registration token is not a valid FCM registration token"));
+    assert!(error_body(r#"FcmResponse { name: "projects/p/messages/1" }"#).is_none());
+}
@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use chrono::Utc;
+use entity::sea_orm_active_enums::PushService;
+use futures::future::join_all;
+use futures::{stream, StreamExt};
+use sea_orm::ActiveValue::Set;
+use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+use tokio::time::{self, Duration};
+use tonic::{Code, Status};
+
+use crate::util::verify;
+
+pub mod digest;
+pub mod email;
+pub mod fcm;
+pub mod push;
+pub mod webhook;
+
+pub type NotifyError = Box<dyn Error + Send + Sync>;
+
+lazy_static! {
+    /// Times a failed notification is sent again
+    static ref RETRIES: u32 = std::env::var("NOTIFY_RETRIES")
+        .unwrap_or("3".to_string())
+        .parse()
+        .unwrap_or(3);
+    /// Delay before the first retry, doubled after every retry
+    static ref RETRY_DELAY_MS: u64 = std::env::var("NOTIFY_RETRY_DELAY_MS")
+        .unwrap_or("1000".to_string())
+        .parse()
+        .unwrap_or(1000);
+    /// Users that are notified at the same time
+    static ref CONCURRENCY: usize = std::env::var("NOTIFY_CONCURRENCY")
+        .unwrap_or("8".to_string())
+        .parse()
+        .unwrap_or(8);
+    /// Client for URLs of users, which may only reach public addresses
+    static ref CLIENT: reqwest::Client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .redirect(reqwest::redirect::Policy::none())
+        .dns_resolver(Arc::new(PublicResolver))
+        .build()
+        .unwrap();
+}
+
+/// New chapters of a manga
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewChapters {
+    pub manga_id: i32,
+    pub manga_title: String,
+    /// In reading order
+    pub chapter_ids: Vec<i32>,
+}
+
+impl NewChapters {
+    pub fn notice(&self) -> Notice {
+        Notice {
+            manga_id: Some(self.manga_id),
+            chapter_ids: self.chapter_ids.clone(),
+            title: "Manga Updated!".to_string(),
+            body: self.manga_title.clone(),
+        }
+    }
+}
+
+/// A notification as it is sent to a user
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notice {
+    /// Not set for a digest of several manga
+    pub manga_id: Option<i32>,
+    pub chapter_ids: Vec<i32>,
+    pub title: String,
+    pub body: String,
+}
+
+/// Error of a target that will never work again, like an unregistered device, so it is not retried
+#[derive(Debug)]
+pub struct InvalidTarget(pub String);
+
+impl std::fmt::Display for InvalidTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid target: {}", self.0)
+    }
+}
+
+impl Error for InvalidTarget {}
+
+/// Resolves hostnames to their public addresses only, so a hostname can not point into the network after it was saved
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| verify::is_public_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(InvalidTarget(format!("{} has no public address", name.as_str())).into());
+            }
+
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// Check a URL of a user again right before it is called, it may point somewhere else than when it was saved
+async fn check_url(url: &str) -> Result<(), NotifyError> {
+    match verify::public_http_url(url).await {
+        Ok(_) => Ok(()),
+        Err(e) if e.code() == Code::InvalidArgument => Err(InvalidTarget(format!("{}: {}", url, e.message())).into()),
+        Err(e) => Err(e.message().into()),
+    }
+}
+
+/// A channel that can deliver notifications to one user
+#[tonic::async_trait]
+pub trait Notifier: Send + Sync {
+    /// Name of the channel, used in logs
+    fn channel(&self) -> &'static str;
+
+    async fn send(&self, notice: &Notice) -> Result<(), NotifyError>;
+}
+
+/// Notification settings of a user, or the defaults if they were never changed
+pub async fn settings(db: &DatabaseConnection, user_id: i32) -> Result<entity::notification_setting::Model, Status> {
+    let settings = entity::notification_setting::Entity::find_by_id(user_id)
+        .one(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(settings.unwrap_or_else(|| default_settings(user_id)))
+}
+
+/// Only FCM is on by default, like before the other channels existed
+fn default_settings(user_id: i32) -> entity::notification_setting::Model {
+    let now = Utc::now().naive_utc();
+    entity::notification_setting::Model {
+        user_id,
+        fcm: true,
+        email: false,
+        webhook_url: None,
+        push_service: PushService::Ntfy,
+        push_url: None,
+        push_token: None,
+        created_at: now,
+        updated_at: now,
+        quiet_start: None,
+        quiet_end: None,
+        timezone: "UTC".to_string(),
+    }
+}
+
+/// The channels a user chose
+pub fn notifiers(
+    db: &DatabaseConnection,
+    user: &entity::user::Model,
+    settings: &entity::notification_setting::Model,
+) -> Vec<Box<dyn Notifier>> {
+    let mut notifiers: Vec<Box<dyn Notifier>> = vec![];
+
+    if settings.fcm {
+        for token in &user.device_ids {
+            notifiers.push(Box::new(fcm::FcmNotifier::new(db.clone(), user.id, token.clone())));
+        }
+    }
+    // Only verified addresses, so nobody can be spammed with someone else's address
+    if settings.email && user.email_verified {
+        notifiers.push(Box::new(email::EmailNotifier::new(user.email.clone())));
+    }
+    if let Some(url) = &settings.webhook_url {
+        notifiers.push(Box::new(webhook::WebhookNotifier::new(url.clone())));
+    }
+    if let Some(url) = &settings.push_url {
+        notifiers.push(Box::new(push::PushNotifier::new(
+            settings.push_service,
+            url.clone(),
+            settings.push_token.clone(),
+        )));
+    }
+
+    notifiers
+}
+
+/// Send a notification, trying again with a growing delay when it fails
+async fn send(notifier: &dyn Notifier, notice: &Notice) -> Result<(), NotifyError> {
+    let mut delay = Duration::from_millis(*RETRY_DELAY_MS);
+    let mut attempt = 0;
+
+    loop {
+        match notifier.send(notice).await {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < *RETRIES && !e.is::<InvalidTarget>() => {
+                warn!(
+                    "[Notify] Failed to send through {} (attempt {}): {}",
+                    notifier.channel(),
+                    attempt + 1,
+                    e
+                );
+                time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Notify a user through all chosen channels, failures are only logged
+pub async fn notify(
+    db: &DatabaseConnection,
+    user: &entity::user::Model,
+    settings: &entity::notification_setting::Model,
+    notice: &Notice,
+) {
+    let notifiers = notifiers(db, user, settings);
+    let results = join_all(notifiers.iter().map(|notifier| send(notifier.as_ref(), notice))).await;
+
+    for (notifier, result) in notifiers.iter().zip(results) {
+        match result {
+            Ok(()) => {}
+            Err(e) if e.is::<InvalidTarget>() => warn!(
+                "[Notify] Gave up on a {} target of user {}: {}",
+                notifier.channel(),
+                user.id,
+                e
+            ),
+            Err(e) => error!(
+                "[Notify] Failed to notify user {} through {}: {}",
+                user.id,
+                notifier.channel(),
+                e
+            ),
+        }
+    }
+}
+
+/// Keep a notification in the inbox of users, so it is not lost when a push is not delivered
+///
+/// Pending notifications are sent later as part of a digest
+async fn store(
+    db: &DatabaseConnection,
+    users: &[entity::user::Model],
+    new_chapters: &NewChapters,
+    pending: bool,
+) -> Result<(), DbErr> {
+    if users.is_empty() {
+        return Ok(());
+    }
+
+    entity::notification::Entity::insert_many(users.iter().map(|user| entity::notification::ActiveModel {
+        user_id: Set(user.id),
+        manga_id: Set(new_chapters.manga_id),
+        chapter_ids: Set(new_chapters.chapter_ids.clone()),
+        pending: Set(pending),
+        ..Default::default()
+    }))
+    .exec_without_returning(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Notification settings of users by their ID
+async fn settings_of(
+    db: &DatabaseConnection,
+    user_ids: impl IntoIterator<Item = i32>,
+) -> Result<HashMap<i32, entity::notification_setting::Model>, DbErr> {
+    Ok(entity::notification_setting::Entity::find()
+        .filter(entity::notification_setting::Column::UserId.is_in(user_ids))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|settings| (settings.user_id, settings))
+        .collect())
+}
+
+/// Add new chapters to the inbox of users and notify them with their own settings
+///
+/// Users in their quiet hours are notified with a digest when their quiet hours end
+pub async fn notify_users(db: &DatabaseConnection, users: Vec<entity::user::Model>, new_chapters: &NewChapters) {
+    let mut settings = match settings_of(db, users.iter().map(|user| user.id)).await {
+        Ok(settings) => settings,
+        Err(e) => {
+            error!("[Notify] Failed to get notification settings: {}", e);
+            return;
+        }
+    };
+
+    let now = Utc::now();
+    let (quiet, awake): (Vec<_>, Vec<_>) = users
+        .into_iter()
+        .map(|user| {
+            let settings = settings.remove(&user.id).unwrap_or_else(|| default_settings(user.id));
+            (user, settings)
+        })
+        .partition(|(_, settings)| digest::in_quiet_hours(settings, now));
+
+    for (users, pending) in [(&quiet, true), (&awake, false)] {
+        let users: Vec<entity::user::Model> = users.iter().map(|(user, _)| user.clone()).collect();
+        if let Err(e) = store(db, &users, new_chapters, pending).await {
+            error!(
+                "[Notify] Failed to store notifications of manga {}: {}",
+                new_chapters.manga_id, e
+            );
+        }
+    }
+
+    info!(
+        "[Notify] Notifying {} users of manga {}, {} in quiet hours",
+        awake.len() + quiet.len(),
+        new_chapters.manga_id,
+        quiet.len()
+    );
+
+    let notice = new_chapters.notice();
+    stream::iter(awake)
+        .for_each_concurrent(*CONCURRENCY, |(user, settings)| {
+            let notice = &notice;
+            async move { notify(db, &user, &settings, notice).await }
+        })
+        .await;
+}
+
+#[test]
+fn notifiers_follow_settings() {
+    let now = Utc::now().naive_utc();
+    let user = entity::user::Model {
+        id: 1,
+        permissions: 0,
+        username: "reader".to_string(),
+        email: "reader@example.com".to_string(),
+        password_hash: String::new(),
+        preferred_hostnames: vec![],
+        created_at: now,
+        updated_at: now,
+        device_ids: vec!["a".to_string(), "b".to_string()],
+        email_verified: false,
+        banned_at: None,
+        ban_reason: None,
+    };
+    let channels = |settings: &entity::notification_setting::Model| -> Vec<&'static str> {
+        notifiers(&DatabaseConnection::Disconnected, &user, settings)
+            .iter()
+            .map(|notifier| notifier.channel())
+            .collect()
+    };
+
+    assert_eq!(channels(&default_settings(1)), vec!["fcm", "fcm"]);
+
+    let settings = entity::notification_setting::Model {
+        fcm: false,
+        // Skipped while the email is not verified
+        email: true,
+        webhook_url: Some("https://example.com/hook".to_string()),
+        push_service: PushService::Gotify,
+        push_url: Some("https://gotify.example.com".to_string()),
+        ..default_settings(1)
+    };
+    assert_eq!(channels(&settings), vec!["webhook", "gotify"]);
+}
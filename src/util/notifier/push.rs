@@ -0,0 +1,63 @@
+use entity::sea_orm_active_enums::PushService;
+use serde_json::json;
+
+use super::{check_url, Notice, Notifier, NotifyError, CLIENT};
+
+/// Push notification through a (self-hosted) ntfy or Gotify server
+pub struct PushNotifier {
+    service: PushService,
+    url: String,
+    token: Option<String>,
+}
+
+impl PushNotifier {
+    pub fn new(service: PushService, url: String, token: Option<String>) -> Self {
+        Self { service, url, token }
+    }
+}
+
+#[tonic::async_trait]
+impl Notifier for PushNotifier {
+    fn channel(&self) -> &'static str {
+        match self.service {
+            PushService::Ntfy => "ntfy",
+            PushService::Gotify => "gotify",
+        }
+    }
+
+    async fn send(&self, notice: &Notice) -> Result<(), NotifyError> {
+        check_url(&self.url).await?;
+
+        let request = match self.service {
+            // The URL is the topic, like https://ntfy.sh/my-topic
+            PushService::Ntfy => {
+                let request = CLIENT
+                    .post(&self.url)
+                    .header("Title", &notice.title)
+                    .body(notice.body.clone());
+                match &self.token {
+                    Some(token) => request.bearer_auth(token),
+                    None => request,
+                }
+            }
+            // The URL is the server, the token is the token of an application
+            PushService::Gotify => {
+                let request = CLIENT
+                    .post(format!("{}/message", self.url.trim_end_matches('/')))
+                    .json(&json!({
+                        "title": notice.title,
+                        "message": notice.body,
+                        "extras": { "manga_id": notice.manga_id },
+                    }));
+                match &self.token {
+                    Some(token) => request.header("X-Gotify-Key", token),
+                    None => request,
+                }
+            }
+        };
+
+        request.send().await?.error_for_status()?;
+
+        Ok(())
+    }
+}
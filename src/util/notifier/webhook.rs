@@ -0,0 +1,39 @@
+use serde_json::json;
+
+use super::{check_url, Notice, Notifier, NotifyError, CLIENT};
+
+/// POSTs every notification as JSON to a URL of the user
+pub struct WebhookNotifier {
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String) -> Self {
+        Self { url }
+    }
+}
+
+#[tonic::async_trait]
+impl Notifier for WebhookNotifier {
+    fn channel(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn send(&self, notice: &Notice) -> Result<(), NotifyError> {
+        check_url(&self.url).await?;
+
+        CLIENT
+            .post(&self.url)
+            .json(&json!({
+                "manga_id": notice.manga_id,
+                "chapter_ids": notice.chapter_ids,
+                "title": notice.title,
+                "body": notice.body,
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
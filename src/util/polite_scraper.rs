@@ -0,0 +1,146 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+use manga_parser::error::ScrapeError;
+use manga_parser::model::{Manga, SearchManga};
+use manga_parser::scraper::scraper_manager::ScraperManager;
+use manga_parser::scraper::MangaScraper;
+use manga_parser::Url;
+use tokio::sync::{Mutex as AsyncMutex, OwnedSemaphorePermit, Semaphore};
+use tokio::time::{self, Duration, Instant};
+
+use crate::util::scraper_config::{host_key, Politeness, HOST_CONFIGS};
+
+lazy_static! {
+    /// Requests that may run at the same time per website
+    static ref HOST_CONCURRENCY: usize = std::env::var("SCRAPER_HOST_CONCURRENCY")
+        .unwrap_or("2".to_string())
+        .parse()
+        .unwrap_or(2);
+    /// Time between the start of two requests to the same website
+    static ref HOST_DELAY_MS: u64 = std::env::var("SCRAPER_HOST_DELAY_MS")
+        .unwrap_or("1000".to_string())
+        .parse()
+        .unwrap_or(1000);
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Limit {
+    concurrency: usize,
+    delay: Duration,
+}
+
+impl Default for Limit {
+    fn default() -> Self {
+        Self {
+            concurrency: *HOST_CONCURRENCY,
+            delay: Duration::from_millis(*HOST_DELAY_MS),
+        }
+    }
+}
+
+impl From<Politeness> for Limit {
+    fn from(value: Politeness) -> Self {
+        let default = Limit::default();
+        Self {
+            concurrency: value.concurrency.unwrap_or(default.concurrency),
+            delay: value.delay_ms.map_or(default.delay, Duration::from_millis),
+        }
+    }
+}
+
+struct Host {
+    permits: Arc<Semaphore>,
+    delay: Duration,
+    /// When the next request may start
+    next: AsyncMutex<Instant>,
+}
+
+/// Scraper that limits the amount of requests and keeps time between them, per website
+pub struct PoliteScraper {
+    scraper: ScraperManager,
+    hosts: Mutex<HashMap<String, Arc<Host>>>,
+}
+
+impl PoliteScraper {
+    pub fn new(scraper: ScraperManager) -> Self {
+        Self {
+            scraper,
+            hosts: Mutex::default(),
+        }
+    }
+
+    fn host(&self, key: &str) -> Arc<Host> {
+        let mut hosts = self.hosts.lock().unwrap();
+        hosts
+            .entry(key.to_string())
+            .or_insert_with(|| {
+                let limit = HOST_CONFIGS
+                    .get(key)
+                    .and_then(|config| config.politeness)
+                    .map(Limit::from)
+                    .unwrap_or_default();
+                Arc::new(Host {
+                    permits: Arc::new(Semaphore::new(limit.concurrency.max(1))),
+                    delay: limit.delay,
+                    next: AsyncMutex::new(Instant::now()),
+                })
+            })
+            .clone()
+    }
+
+    /// Wait for a free slot of a website and until enough time passed since its last request
+    async fn acquire(&self, key: &str) -> OwnedSemaphorePermit {
+        let host = self.host(key);
+        let permit = host
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("host semaphores are never closed");
+
+        let mut next = host.next.lock().await;
+        time::sleep_until(*next).await;
+        *next = Instant::now() + host.delay;
+
+        permit
+    }
+
+    /// Scrape a manga with its chapters
+    pub async fn manga(&self, url: &Url) -> Result<Manga, ScrapeError> {
+        let _permit = self.acquire(&host_key(url.as_str())).await;
+        self.scraper.manga(url).await
+    }
+
+    /// Scrape the image URLs of a chapter
+    pub async fn chapter_images(&self, url: &Url) -> Result<Vec<Url>, ScrapeError> {
+        let _permit = self.acquire(&host_key(url.as_str())).await;
+        self.scraper.chapter_images(url).await
+    }
+
+    /// Search on the given websites, or on all searchable websites if none are given
+    pub async fn search(&self, keyword: &str, hostnames: &[String]) -> Result<Vec<SearchManga>, ScrapeError> {
+        let keys: BTreeSet<String> = if hostnames.is_empty() {
+            self.scraper
+                .searchable_hostnames()
+                .iter()
+                .map(|url| host_key(url.as_str()))
+                .collect()
+        } else {
+            hostnames.iter().map(|hostname| host_key(hostname)).collect()
+        };
+
+        // Always in the same (sorted) order, so two searches can not wait on each other
+        let mut permits = Vec::with_capacity(keys.len());
+        for key in &keys {
+            permits.push(self.acquire(key).await);
+        }
+
+        self.scraper.search(keyword, hostnames).await
+    }
+
+    /// Websites that can be searched
+    pub fn searchable_hostnames(&self) -> Vec<Url> {
+        self.scraper.searchable_hostnames()
+    }
+}
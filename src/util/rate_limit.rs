@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tonic::metadata::MetadataValue;
+use tonic::Status;
+
+/// Forget about buckets and failures that are not relevant anymore when there are more than this
+const MAX_ENTRIES: usize = 10000;
+
+lazy_static! {
+    /// Amount of requests that can be made at once
+    static ref RATE_LIMIT_BURST: f64 = std::env::var("RATE_LIMIT_BURST")
+        .unwrap_or("5".to_string())
+        .parse()
+        .unwrap_or(5.0);
+    /// Amount of requests that can be made per minute after the burst is used up
+    static ref RATE_LIMIT_PER_MINUTE: f64 = std::env::var("RATE_LIMIT_PER_MINUTE")
+        .unwrap_or("10".to_string())
+        .parse()
+        .unwrap_or(10.0);
+    /// Failed logins before an account gets locked
+    static ref LOGIN_MAX_FAILURES: u32 = std::env::var("LOGIN_MAX_FAILURES")
+        .unwrap_or("5".to_string())
+        .parse()
+        .unwrap_or(5);
+    /// How long an account stays locked (default 15 minutes)
+    static ref LOGIN_LOCKOUT_MS: u64 = std::env::var("LOGIN_LOCKOUT_MS")
+        .unwrap_or("900000".to_string())
+        .parse()
+        .unwrap_or(900000);
+
+    /// Rate limit per remote address
+    pub static ref ADDRESS_LIMITER: RateLimiter<IpAddr> = RateLimiter::new(*RATE_LIMIT_BURST, *RATE_LIMIT_PER_MINUTE);
+    /// Rate limit per username or email that is used to log in
+    pub static ref USERNAME_LIMITER: RateLimiter<String> = RateLimiter::new(*RATE_LIMIT_BURST, *RATE_LIMIT_PER_MINUTE);
+    /// Accounts that failed to log in too often
+    pub static ref LOGIN_LOCKOUT: Lockout<i32> = Lockout::new(*LOGIN_MAX_FAILURES, Duration::from_millis(*LOGIN_LOCKOUT_MS));
+}
+
+/// Create a resource exhausted status telling the client when to retry
+pub fn exhausted(message: &str, retry_after: Duration) -> Status {
+    let mut status = Status::resource_exhausted(message);
+    // Round up so clients never retry too early
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    status
+        .metadata_mut()
+        .insert("retry-after", MetadataValue::from(seconds));
+    status
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Token bucket rate limiter
+///
+/// Every key gets a bucket that holds `capacity` tokens and refills over time,
+/// each request takes one token
+pub struct RateLimiter<K> {
+    capacity: f64,
+    /// Tokens added per second
+    refill_rate: f64,
+    buckets: Mutex<HashMap<K, Bucket>>,
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+    pub fn new(capacity: f64, per_minute: f64) -> Self {
+        Self {
+            capacity: capacity.max(1.0),
+            refill_rate: per_minute.max(f64::EPSILON) / 60.0,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token for this key
+    ///
+    /// Returns how long to wait if the bucket is empty
+    pub fn check(&self, key: K) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() > MAX_ENTRIES {
+            // Full buckets behave the same as new ones
+            let capacity = self.capacity;
+            let refill_rate = self.refill_rate;
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated_at).as_secs_f64() * refill_rate < capacity
+            });
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: self.capacity,
+            updated_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.refill_rate).min(self.capacity);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.refill_rate))
+        }
+    }
+}
+
+struct Failures {
+    count: u32,
+    locked_until: Option<Instant>,
+}
+
+/// Locks keys for a while after too many failures
+pub struct Lockout<K> {
+    max_failures: u32,
+    duration: Duration,
+    failures: Mutex<HashMap<K, Failures>>,
+}
+
+impl<K: Eq + Hash> Lockout<K> {
+    pub fn new(max_failures: u32, duration: Duration) -> Self {
+        Self {
+            max_failures: max_failures.max(1),
+            duration,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check if a key is locked
+    ///
+    /// Returns how long the lock will last
+    pub fn check(&self, key: &K) -> Result<(), Duration> {
+        let now = Instant::now();
+        let failures = self.failures.lock().unwrap();
+
+        match failures.get(key).and_then(|failures| failures.locked_until) {
+            Some(locked_until) if locked_until > now => Err(locked_until - now),
+            _ => Ok(()),
+        }
+    }
+
+    /// Register a failure, locks the key when there were too many
+    pub fn fail(&self, key: K) {
+        let now = Instant::now();
+        let mut failures = self.failures.lock().unwrap();
+
+        if failures.len() > MAX_ENTRIES {
+            failures.retain(|_, failures| failures.locked_until.is_some_and(|locked_until| locked_until > now));
+        }
+
+        let entry = failures.entry(key).or_insert(Failures {
+            count: 0,
+            locked_until: None,
+        });
+
+        // Start counting again after a lock expired
+        if entry.locked_until.is_some_and(|locked_until| locked_until <= now) {
+            entry.count = 0;
+            entry.locked_until = None;
+        }
+
+        entry.count += 1;
+        if entry.count >= self.max_failures {
+            entry.locked_until = Some(now + self.duration);
+        }
+    }
+
+    /// Forget all failures of a key
+    pub fn reset(&self, key: &K) {
+        self.failures.lock().unwrap().remove(key);
+    }
+}
+
+#[test]
+fn rate_limit_and_lockout() {
+    let limiter = RateLimiter::new(2.0, 1.0);
+    assert!(limiter.check("owo").is_ok());
+    assert!(limiter.check("owo").is_ok());
+    let retry_after = limiter.check("owo").unwrap_err();
+    assert!(retry_after > Duration::from_secs(59) && retry_after <= Duration::from_secs(60));
+    assert!(limiter.check("uwu").is_ok());
+
+    let lockout = Lockout::new(2, Duration::from_secs(60));
+    lockout.fail(1);
+    assert!(lockout.check(&1).is_ok());
+    lockout.fail(1);
+    assert!(lockout.check(&1).is_err());
+    lockout.reset(&1);
+    assert!(lockout.check(&1).is_ok());
+}
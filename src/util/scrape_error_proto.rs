@@ -20,11 +20,15 @@ pub struct StatusWrapper(pub Status);
 
 impl From<ScrapeError> for StatusWrapper {
     fn from(value: ScrapeError) -> Self {
-        let message: String = value.to_string();
+        proto::ScrapeError::from(value).into()
+    }
+}
+
+impl From<proto::ScrapeError> for StatusWrapper {
+    fn from(value: proto::ScrapeError) -> Self {
+        let message: String = value.message.clone();
         let mut buffer = BytesMut::with_capacity(4096);
-        Into::<proto::ScrapeError>::into(value)
-            .encode(&mut buffer)
-            .expect("encode error");
+        value.encode(&mut buffer).expect("encode error");
 
         let details = proto::DetailedError {
             status: tonic::Code::Internal as i32,
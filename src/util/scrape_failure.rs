@@ -0,0 +1,114 @@
+use chrono::Duration;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use tonic::Status;
+
+use crate::proto::{self, MangaHealthReply, ScrapeErrorType, ScrapeFailureReply};
+use crate::util::update_job;
+
+lazy_static! {
+    /// Wait this long after a failed update before trying again, doubled for every next failure
+    pub static ref FAILURE_BACKOFF_MS: i64 = std::env::var("MANGA_FAILURE_BACKOFF_MS")
+        .unwrap_or("3600000".to_string())
+        .parse()
+        .unwrap_or(3600000);
+    /// The longest wait between attempts of a failing manga
+    pub static ref FAILURE_BACKOFF_MAX_MS: i64 = std::env::var("MANGA_FAILURE_BACKOFF_MAX_MS")
+        .unwrap_or("604800000".to_string())
+        .parse()
+        .unwrap_or(604800000);
+}
+
+/// If the last update of the manga failed
+pub const FAILING_QUERY: &str = r#"
+EXISTS (
+    SELECT 1 FROM scrape_failure
+    WHERE scrape_failure.manga_id = manga.id AND scrape_failure.created_at > manga.updated_at
+)"#;
+
+/// Amount of failures that are shown in the health of a manga
+const HEALTH_FAILURES: u64 = 20;
+
+/// Save a failed update of a manga
+pub async fn record(db: &DatabaseConnection, manga_id: i32, error: &proto::ScrapeError) -> Result<(), Status> {
+    warn!(
+        "Scraping manga {} failed with {}: {}",
+        manga_id,
+        error.r#type().as_str_name(),
+        error.message
+    );
+
+    entity::scrape_failure::ActiveModel {
+        manga_id: Set(manga_id),
+        error_type: Set(error.r#type().as_str_name().to_string()),
+        message: Set(error.message.clone()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Time to wait before the next attempt after a number of failures in a row
+pub fn backoff(failures: i64) -> Duration {
+    let exponent = (failures - 1).clamp(0, 32) as u32;
+    let ms = FAILURE_BACKOFF_MS.saturating_mul(2_i64.saturating_pow(exponent));
+
+    Duration::milliseconds(ms.min(*FAILURE_BACKOFF_MAX_MS))
+}
+
+/// Get the recent failures of a manga and when it will be updated again
+pub async fn health(db: &DatabaseConnection, manga: &entity::manga::Model) -> Result<MangaHealthReply, Status> {
+    use entity::scrape_failure::Column;
+
+    let failures = entity::scrape_failure::Entity::find()
+        .filter(Column::MangaId.eq(manga.id))
+        .order_by_desc(Column::Id)
+        .limit(HEALTH_FAILURES)
+        .all(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    let consecutive_failures = entity::scrape_failure::Entity::find()
+        .filter(Column::MangaId.eq(manga.id))
+        .filter(Column::CreatedAt.gt(manga.updated_at))
+        .select_only()
+        .column_as(Column::Id.count(), "count")
+        .into_tuple::<i64>()
+        .one(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .unwrap_or_default();
+
+    let next_attempt = update_job::find(db, manga.id)
+        .await?
+        .map(|job| job.run_at.and_utc().timestamp_millis());
+
+    Ok(MangaHealthReply {
+        manga_id: manga.id,
+        failing: consecutive_failures > 0,
+        consecutive_failures,
+        last_success: manga.updated_at.and_utc().timestamp_millis(),
+        next_attempt,
+        failures: failures
+            .into_iter()
+            .map(|failure| ScrapeFailureReply {
+                id: failure.id,
+                r#type: ScrapeErrorType::from_str_name(&failure.error_type)
+                    .unwrap_or_default()
+                    .into(),
+                message: failure.message,
+                created_at: failure.created_at.and_utc().timestamp_millis(),
+            })
+            .collect(),
+    })
+}
+
+#[test]
+fn backoff_doubles_up_to_max() {
+    assert_eq!(backoff(1), Duration::milliseconds(*FAILURE_BACKOFF_MS));
+    assert_eq!(backoff(2), Duration::milliseconds(*FAILURE_BACKOFF_MS * 2));
+    assert_eq!(backoff(1000), Duration::milliseconds(*FAILURE_BACKOFF_MAX_MS));
+}
@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use manga_parser::Url;
+use serde::Deserialize;
+
+lazy_static! {
+    /// Settings of the scraper configs per website
+    pub static ref HOST_CONFIGS: HashMap<String, HostConfig> = load(
+        &std::env::var("SCRAPER_CONFIG_DIR").unwrap_or("configs".to_string())
+    );
+}
+
+/// The parts of a scraper config that are used outside of the scraper
+#[derive(Debug, Deserialize)]
+struct Config {
+    #[serde(default)]
+    accept: Accept,
+    #[serde(default)]
+    search: Vec<Search>,
+    politeness: Option<Politeness>,
+    #[serde(default)]
+    image_headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Accept {
+    #[serde(default)]
+    hostnames: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Search {
+    #[serde(default)]
+    hostnames: Vec<String>,
+}
+
+/// Limits for requests to a website
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Politeness {
+    pub concurrency: Option<usize>,
+    pub delay_ms: Option<u64>,
+}
+
+/// Settings of the scraper config of one website
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostConfig {
+    pub politeness: Option<Politeness>,
+    /// Extra headers needed to load images, like a Referer
+    pub image_headers: HashMap<String, String>,
+}
+
+/// The website of a hostname or URL, so "https://www.example.com/manga" and "example.com" share their settings
+pub fn host_key(value: &str) -> String {
+    let hostname = Url::parse(value)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or(value.to_string())
+        .to_lowercase();
+
+    hostname.strip_prefix("www.").unwrap_or(&hostname).to_string()
+}
+
+/// Settings for all hostnames of a scraper config
+fn parse(yaml: &str) -> Result<Vec<(String, HostConfig)>, serde_yaml::Error> {
+    let config: Config = serde_yaml::from_str(yaml)?;
+    let host_config = HostConfig {
+        politeness: config.politeness,
+        image_headers: config.image_headers,
+    };
+
+    Ok(config
+        .accept
+        .hostnames
+        .iter()
+        .chain(config.search.iter().flat_map(|search| &search.hostnames))
+        .map(|hostname| (host_key(hostname), host_config.clone()))
+        .collect())
+}
+
+/// Read all scraper configs in a directory
+fn load(dir: &str) -> HashMap<String, HostConfig> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Could not read scraper configs in {}: {}", dir, e);
+            return HashMap::new();
+        }
+    };
+
+    let mut configs = HashMap::new();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if !path
+            .extension()
+            .is_some_and(|extension| extension == "yaml" || extension == "yml")
+        {
+            continue;
+        }
+
+        let res = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|yaml| parse(&yaml).map_err(|e| e.to_string()));
+        match res {
+            Ok(config) => configs.extend(config),
+            Err(e) => warn!("Could not read scraper config {}: {}", path.display(), e),
+        }
+    }
+
+    configs
+}
+
+#[test]
+fn parse_config() {
+    let configs = parse(
+        r#"
+name: example
+accept:
+  hostnames:
+    - www.Example.com
+search:
+  - hostnames:
+      - search.example.com
+politeness:
+  concurrency: 1
+image_headers:
+  Referer: https://example.com/
+"#,
+    )
+    .unwrap();
+
+    let config = HostConfig {
+        politeness: Some(Politeness {
+            concurrency: Some(1),
+            delay_ms: None,
+        }),
+        image_headers: HashMap::from([("Referer".to_string(), "https://example.com/".to_string())]),
+    };
+    assert_eq!(
+        configs,
+        vec![
+            ("example.com".to_string(), config.clone()),
+            ("search.example.com".to_string(), config)
+        ]
+    );
+    assert_eq!(parse("name: other\naccept: {}").unwrap(), vec![]);
+}
@@ -15,6 +15,7 @@ pub enum SearchField {
     Date(&'static str, bool),
     Equals(&'static str),
     Number(&'static str),
+    Bool(&'static str),
 }
 
 impl ToString for SearchField {
@@ -25,6 +26,7 @@ impl ToString for SearchField {
             SearchField::Date(s, _) => s.to_string(),
             SearchField::Equals(s) => s.to_string(),
             SearchField::Number(s) => s.to_string(),
+            SearchField::Bool(s) => s.to_string(),
         }
     }
 }
@@ -70,6 +72,20 @@ impl SearchField {
             SearchField::Equals(ident) => {
                 expr += &format!("{ident} = $1");
             }
+            SearchField::Bool(ident) => {
+                let value = match value.to_ascii_lowercase().as_str() {
+                    "true" | "yes" => true,
+                    "false" | "no" => false,
+                    _ => {
+                        return Err(Status::invalid_argument(format!(
+                            "Expected true or false but got {value}"
+                        )))
+                    }
+                };
+                expr += &format!("({ident}) = $1");
+
+                return Ok(Expr::cust_with_values(&expr, vec![value]));
+            }
             SearchField::Number(ident) => {
                 let captures = SEARCH_DATE_REGEX
                     .captures(value)
@@ -4,6 +4,7 @@ use tonic::Status;
 use super::field::SearchField;
 use super::parse::Search;
 use crate::service::v1::manga::NEXT_UPDATE_QUERY;
+use crate::util::scrape_failure::FAILING_QUERY;
 
 const SELECT_MANGA_ALL: &str = r#"
 ARRAY_TO_STRING(manga.genres, ', ')     || ' ' ||
@@ -12,6 +13,14 @@ ARRAY_TO_STRING(manga.alt_titles, ', ') || ' ' ||
 manga.description                       || ' ' ||
 manga.title"#;
 
+/// Names of the collections of the logged in user that contain the manga
+const SELECT_COLLECTIONS: &str = r#"
+COALESCE((
+    SELECT STRING_AGG(collection.name, ', ') FROM collection
+    JOIN collection_manga ON collection_manga.collection_id = collection.id
+    WHERE collection_manga.manga_id = manga.id AND collection.user_id = reading.user_id
+), '')"#;
+
 static SEARCH_FIELDS: phf::Map<&'static str, SearchField> = phf_map! {
     "title" => SearchField::Text("ARRAY_TO_STRING(manga.alt_titles, ', ') || ' ' || manga.title"),
     "description" => SearchField::Text("manga.description"),
@@ -27,6 +36,9 @@ static SEARCH_FIELDS: phf::Map<&'static str, SearchField> = phf_map! {
     "chapters" => SearchField::Number("COUNT(chapter.id)"),
     "progress" => SearchField::Number("reading.progress"),
     "reading" => SearchField::Number("reading.progress"),
+    "status" => SearchField::Equals("reading.status::text"),
+    "collection" => SearchField::Text(SELECT_COLLECTIONS),
+    "failing" => SearchField::Bool(FAILING_QUERY),
     "*" => SearchField::Text(SELECT_MANGA_ALL),
 };
 
@@ -0,0 +1,83 @@
+use chrono::Utc;
+use migration::Expr;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use tonic::{Request, Status};
+
+/// Only update the last seen time of a session once a minute
+const LAST_SEEN_INTERVAL_MS: i64 = 60000;
+
+/// Information about the client that is logging in
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub remote_addr: Option<String>,
+}
+
+impl<T> From<&Request<T>> for ClientInfo {
+    fn from(request: &Request<T>) -> Self {
+        Self {
+            user_agent: request
+                .metadata()
+                .get("user-agent")
+                .and_then(|user_agent| user_agent.to_str().ok())
+                .map(|user_agent| user_agent.chars().take(511).collect()),
+            remote_addr: request.remote_addr().map(|addr| addr.ip().to_string()),
+        }
+    }
+}
+
+/// Start a new session for a user
+pub async fn create(
+    db: &DatabaseConnection,
+    user_id: i32,
+    client: ClientInfo,
+) -> Result<entity::session::Model, Status> {
+    entity::session::ActiveModel {
+        user_id: Set(user_id),
+        user_agent: Set(client.user_agent),
+        remote_addr: Set(client.remote_addr),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .map_err(|e| Status::internal(e.to_string()))
+}
+
+/// Update the last seen time and address of a session
+pub async fn touch(
+    db: &DatabaseConnection,
+    session: &entity::session::Model,
+    remote_addr: Option<String>,
+) -> Result<(), Status> {
+    let now = Utc::now().naive_utc();
+    if (now - session.last_seen_at).num_milliseconds() < LAST_SEEN_INTERVAL_MS {
+        return Ok(());
+    }
+
+    entity::session::Entity::update_many()
+        .col_expr(entity::session::Column::LastSeenAt, Expr::value(now))
+        .col_expr(
+            entity::session::Column::RemoteAddr,
+            Expr::value(remote_addr.or(session.remote_addr.clone())),
+        )
+        .filter(entity::session::Column::Id.eq(session.id))
+        .exec(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(())
+}
+
+/// End a session of a user
+///
+/// Returns false if the session did not exist
+pub async fn revoke(db: &DatabaseConnection, user_id: i32, session_id: i32) -> Result<bool, Status> {
+    let res = entity::session::Entity::delete_many()
+        .filter(entity::session::Column::Id.eq(session_id))
+        .filter(entity::session::Column::UserId.eq(user_id))
+        .exec(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(res.rows_affected > 0)
+}
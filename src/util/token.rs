@@ -0,0 +1,124 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::Utc;
+use migration::Expr;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use sha2::{Digest, Sha256};
+use tonic::Status;
+
+use crate::interceptor::auth::sign;
+use crate::proto::{AuthError, AuthErrorType};
+use crate::util::session;
+
+lazy_static! {
+    /// How long an access token stays valid (default 15 minutes)
+    static ref ACCESS_TOKEN_TTL_MS: i64 = std::env::var("JWT_ACCESS_TOKEN_TTL_MS")
+        .unwrap_or("900000".to_string())
+        .parse()
+        .unwrap_or(900000);
+    /// How long a refresh token stays valid (default 30 days)
+    static ref REFRESH_TOKEN_TTL_MS: i64 = std::env::var("JWT_REFRESH_TOKEN_TTL_MS")
+        .unwrap_or("2592000000".to_string())
+        .parse()
+        .unwrap_or(2592000000);
+}
+
+/// Access and refresh token handed to a client
+pub struct TokenPair {
+    pub user_id: i32,
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Expiry of the access token in milliseconds
+    pub expires_at: i64,
+}
+
+/// Generate a random opaque token
+pub fn generate() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Hash a token, only the hash is stored in the database
+pub fn hash(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Issue a new access and refresh token for a user's session
+pub async fn issue(db: &DatabaseConnection, user_id: i32, session_id: i32) -> Result<TokenPair, Status> {
+    let now = Utc::now();
+    let refresh_token = generate();
+
+    // Clean up refresh tokens that can not be used anymore
+    entity::refresh_token::Entity::delete_many()
+        .filter(entity::refresh_token::Column::UserId.eq(user_id))
+        .filter(entity::refresh_token::Column::ExpiresAt.lt(now.naive_utc()))
+        .exec(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    let saved = entity::refresh_token::ActiveModel {
+        user_id: Set(user_id),
+        session_id: Set(session_id),
+        token_hash: Set(hash(&refresh_token)),
+        expires_at: Set((now + chrono::Duration::milliseconds(*REFRESH_TOKEN_TTL_MS)).naive_utc()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .map_err(|e| Status::internal(e.to_string()))?;
+
+    let expires_at = now + chrono::Duration::milliseconds(*ACCESS_TOKEN_TTL_MS);
+    let access_token = sign(user_id, session_id, saved.id, now.timestamp(), expires_at.timestamp())
+        .map_err(|e| Status::aborted(e.to_string()))?;
+
+    Ok(TokenPair {
+        user_id,
+        access_token,
+        refresh_token,
+        expires_at: expires_at.timestamp_millis(),
+    })
+}
+
+/// Exchange a refresh token for a new token pair
+///
+/// The used refresh token is revoked, if a revoked token is used again
+/// it probably leaked and the session it belongs to is ended
+pub async fn rotate(db: &DatabaseConnection, refresh_token: &str) -> Result<TokenPair, Status> {
+    use entity::refresh_token::Column;
+
+    let stored = entity::refresh_token::Entity::find()
+        .filter(Column::TokenHash.eq(hash(refresh_token)))
+        .one(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .ok_or(AuthError::new(AuthErrorType::TokenInvalid, "Refresh token is invalid"))?;
+
+    if stored.expires_at < Utc::now().naive_utc() {
+        return Err(AuthError::new(AuthErrorType::TokenExpired, "Refresh token has expired").into());
+    }
+
+    // Only one request can revoke the token, so it can not be used twice
+    let res = entity::refresh_token::Entity::update_many()
+        .col_expr(Column::Revoked, Expr::value(true))
+        .filter(Column::Id.eq(stored.id))
+        .filter(Column::Revoked.eq(false))
+        .exec(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    if res.rows_affected == 0 {
+        warn!(
+            "Revoked refresh token of user {} was used again, ending session {}",
+            stored.user_id, stored.session_id
+        );
+        session::revoke(db, stored.user_id, stored.session_id).await?;
+
+        return Err(AuthError::new(AuthErrorType::TokenRevoked, "Refresh token has been revoked").into());
+    }
+
+    issue(db, stored.user_id, stored.session_id).await
+}
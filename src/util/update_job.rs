@@ -0,0 +1,178 @@
+use chrono::Utc;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait, QueryFilter, Statement, TransactionTrait,
+};
+use tonic::Status;
+
+use crate::service::v1::manga::NEXT_UPDATE_QUERY;
+use crate::util::scrape_failure;
+
+lazy_static! {
+    /// Only update manga that have not been updated for this long
+    pub static ref MIN_INTERVAL_MS: i64 = std::env::var("MANGA_AUTO_UPDATE_MIN_INTERVAL_MS")
+        .unwrap_or("28800000".to_string())
+        .parse()
+        .unwrap_or(28800000);
+    /// Amount of manga of the same website that are updated at the same time
+    pub static ref HOST_CONCURRENCY: i64 = std::env::var("MANGA_AUTO_UPDATE_HOST_CONCURRENCY")
+        .unwrap_or("1".to_string())
+        .parse()
+        .unwrap_or(1);
+    /// A job that is running for longer than this is given to another worker, in case its worker stopped
+    pub static ref JOB_TIMEOUT_MS: i64 = std::env::var("MANGA_AUTO_UPDATE_TIMEOUT_MS")
+        .unwrap_or("1800000".to_string())
+        .parse()
+        .unwrap_or(1800000);
+}
+
+/// Manga that somebody is still waiting for new chapters of
+const READ_QUERY: &str = r#"
+EXISTS (
+    SELECT 1 FROM reading
+    WHERE reading.manga_id = manga.id AND reading.status NOT IN ('dropped', 'completed')
+)"#;
+
+/// Key of the advisory lock that makes claiming a job one worker at a time, also across servers
+const CLAIM_LOCK_KEY: i64 = 0x7570_6461_7465;
+
+/// Take the next job that should run and of which the website is not busy with too many other jobs
+///
+/// $1 is the worker, $2 the job timeout and $3 the concurrency per host
+const CLAIM_QUERY: &str = r#"
+UPDATE update_job SET locked_at = NOW(), locked_by = $1
+WHERE id = (
+    SELECT job.id FROM update_job job
+    WHERE job.run_at <= NOW()
+    AND (job.locked_at IS NULL OR job.locked_at < NOW() - $2 * INTERVAL '1 millisecond')
+    AND (
+        SELECT COUNT(*) FROM update_job running
+        WHERE running.host = job.host AND running.locked_at >= NOW() - $2 * INTERVAL '1 millisecond'
+    ) < $3
+    ORDER BY job.run_at
+    LIMIT 1
+)
+RETURNING *"#;
+
+/// Add a job for every read manga that does not have one yet
+///
+/// The job runs when the next chapter is expected, but not before the minimum interval ($1) has passed.
+/// $2 limits it to one manga
+fn enqueue_query() -> String {
+    format!(
+        r#"
+INSERT INTO update_job (manga_id, host, run_at)
+SELECT
+    manga.id,
+    COALESCE(SUBSTRING(manga.url FROM '^[^:]+://([^/:?#]+)'), ''),
+    GREATEST(manga.updated_at + $1 * INTERVAL '1 millisecond', {NEXT_UPDATE_QUERY})
+FROM manga
+LEFT JOIN chapter ON chapter.manga_id = manga.id AND chapter.deleted_at IS NULL
+WHERE ($2::int IS NULL OR manga.id = $2) AND {READ_QUERY}
+GROUP BY manga.id
+ON CONFLICT (manga_id) DO NOTHING"#
+    )
+}
+
+/// Schedule an update for read manga, returns the amount of new jobs
+pub async fn enqueue(db: &DatabaseConnection, manga_id: Option<i32>) -> Result<u64, Status> {
+    let res = db
+        .execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            enqueue_query(),
+            [(*MIN_INTERVAL_MS).into(), manga_id.into()],
+        ))
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(res.rows_affected())
+}
+
+/// Remove waiting jobs of manga that nobody reads anymore
+pub async fn prune(db: &DatabaseConnection) -> Result<u64, Status> {
+    let res = db
+        .execute(Statement::from_string(
+            DbBackend::Postgres,
+            format!(
+                "DELETE FROM update_job USING manga WHERE manga.id = update_job.manga_id AND update_job.locked_at IS NULL AND NOT {READ_QUERY}"
+            ),
+        ))
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(res.rows_affected())
+}
+
+/// Lock the next job for a worker
+pub async fn claim(db: &DatabaseConnection, worker: &str) -> Result<Option<entity::update_job::Model>, Status> {
+    let txn = db.begin().await.map_err(|e| Status::internal(e.to_string()))?;
+
+    // Without this two workers could both see room for one more job of the same website
+    txn.execute(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "SELECT pg_advisory_xact_lock($1)",
+        [CLAIM_LOCK_KEY.into()],
+    ))
+    .await
+    .map_err(|e| Status::internal(e.to_string()))?;
+
+    let job = entity::update_job::Entity::find()
+        .from_raw_sql(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            CLAIM_QUERY,
+            [worker.into(), (*JOB_TIMEOUT_MS).into(), (*HOST_CONCURRENCY).into()],
+        ))
+        .one(&txn)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    txn.commit().await.map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(job)
+}
+
+/// Finish a job and schedule the next update of its manga
+pub async fn complete(db: &DatabaseConnection, job: &entity::update_job::Model) -> Result<(), Status> {
+    entity::update_job::Entity::delete_many()
+        .filter(entity::update_job::Column::Id.eq(job.id))
+        .filter(entity::update_job::Column::LockedBy.eq(job.locked_by.clone()))
+        .exec(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    enqueue(db, Some(job.manga_id)).await?;
+
+    Ok(())
+}
+
+/// Unlock a failed job and try again later, waiting longer after every failure
+pub async fn retry(db: &DatabaseConnection, job: &entity::update_job::Model, error: &str) -> Result<(), Status> {
+    let attempts = job.attempts + 1;
+
+    // Only while this worker still holds the lock, it may have timed out and been claimed by another worker
+    entity::update_job::Entity::update_many()
+        .set(entity::update_job::ActiveModel {
+            run_at: Set(Utc::now().naive_utc() + scrape_failure::backoff(attempts.into())),
+            attempts: Set(attempts),
+            locked_at: Set(None),
+            locked_by: Set(None),
+            last_error: Set(Some(error.to_string())),
+            ..Default::default()
+        })
+        .filter(entity::update_job::Column::Id.eq(job.id))
+        .filter(entity::update_job::Column::LockedBy.eq(job.locked_by.clone()))
+        .exec(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(())
+}
+
+/// The waiting or running job of a manga
+pub async fn find(db: &DatabaseConnection, manga_id: i32) -> Result<Option<entity::update_job::Model>, Status> {
+    entity::update_job::Entity::find()
+        .filter(entity::update_job::Column::MangaId.eq(manga_id))
+        .one(db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))
+}
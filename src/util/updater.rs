@@ -1,130 +1,250 @@
 use chrono::Utc;
-use fcm::message::{Message, Notification, Target};
-use fcm::FcmClient;
+use entity::sea_orm_active_enums::ReadingStatus;
 use manga_parser::Url;
-use migration::{Expr, JoinType};
-use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect, RelationTrait};
-use serde_json::json;
+use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait};
 use tokio::time::{self, Duration};
 
-use crate::data;
-use crate::service::v1::manga::{index_manga, save_manga};
+use crate::proto::{self, MangaReply, ScrapeErrorType};
+use crate::service::v1::manga::{get_manga_by_id, save_manga};
+use crate::util::notifier::{self, NewChapters};
+use crate::util::{scrape_failure, update_job};
 
+/// Start the scheduler and the workers that update manga in the background
 pub async fn watch_updates(db: &DatabaseConnection) {
-    let interval_ms: u64 = std::env::var("MANGA_AUTO_UPDATE_INTERVAL_MS")
-        .unwrap_or("14400000".to_string())
+    let schedule_interval_ms: u64 = std::env::var("MANGA_AUTO_UPDATE_SCHEDULE_INTERVAL_MS")
+        .unwrap_or("600000".to_string())
         .parse()
-        .unwrap_or(14400000);
+        .unwrap_or(600000);
+    let workers: usize = std::env::var("MANGA_AUTO_UPDATE_WORKERS")
+        .unwrap_or("4".to_string())
+        .parse()
+        .unwrap_or(4);
+
+    // Unique across restarts and servers sharing the database
+    let prefix = format!("{}-{}", std::process::id(), Utc::now().timestamp_millis());
+    for worker in 0..workers.max(1) {
+        let db = db.clone();
+        let worker = format!("{prefix}-{worker}");
+        tokio::spawn(async move {
+            work(&db, &worker).await;
+        });
+    }
 
-    let mut interval = time::interval(Duration::from_millis(interval_ms));
+    let mut interval = time::interval(Duration::from_millis(schedule_interval_ms));
 
     loop {
         interval.tick().await;
 
-        let update_list = collect_priority_manga(db).await;
-
-        info!("[Auto Update] Found {} manga that should be updated", update_list.len());
-        for manga in update_list {
-            info!("[Auto Update] Automatically updating {}", manga.url);
-            let url = Url::parse(&manga.url);
-            match url {
-                Ok(url) => {
-                    let saved = save_manga(db, None, Some(manga.id), url).await;
-                    match saved {
-                        Ok(saved) => {
-                            if saved.count_chapters != manga.count_chapters {
-                                let readers = get_readers(db, manga.id).await;
-                                let ids: Vec<String> = readers.into_iter().flat_map(|user| user.device_ids).collect();
-
-                                info!("[Auto Update] Successfully updated {}", saved.url.to_string());
-                                if !ids.is_empty() {
-                                    send_notification(&manga, ids.as_slice()).await;
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            error!("[Auto Update] URL Failed to Parse: {:#?}", e);
-                        }
-                    }
-                }
-                Err(e) => error!("[Auto Update] URL Failed to Parse: {:#?}", e),
-            }
+        match update_job::prune(db).await {
+            Ok(0) => {}
+            Ok(pruned) => info!(
+                "[Auto Update] Removed {} jobs of manga that are not read anymore",
+                pruned
+            ),
+            Err(e) => error!("[Auto Update] Failed to remove jobs: {}", e.message()),
+        }
+        match update_job::enqueue(db, None).await {
+            Ok(0) => {}
+            Ok(added) => info!("[Auto Update] Scheduled {} manga for updates", added),
+            Err(e) => error!("[Auto Update] Failed to schedule updates: {}", e.message()),
         }
     }
 }
 
-async fn collect_priority_manga(db: &DatabaseConnection) -> Vec<data::manga::Full> {
-    // select 10 manga sorted by highest reading count
-    // select only ones that havent been reloaded for at least "MANGA_AUTO_UPDATE_MIN_INTERVAL" milliseconds
-    let min_interval: i64 = std::env::var("MANGA_AUTO_UPDATE_MIN_INTERVAL_MS")
-        .unwrap_or("28800000".to_string())
-        .parse()
-        .unwrap_or(28800000);
-    let limit: u64 = std::env::var("MANGA_AUTO_UPDATE_MAX")
-        .unwrap_or("10".to_string())
+/// Keep running jobs that are due
+async fn work(db: &DatabaseConnection, worker: &str) {
+    let poll_interval_ms: u64 = std::env::var("MANGA_AUTO_UPDATE_POLL_INTERVAL_MS")
+        .unwrap_or("30000".to_string())
         .parse()
-        .unwrap_or(10);
-
-    use entity::{manga, reading};
-
-    let date_time = Utc::now().checked_sub_signed(chrono::Duration::milliseconds(min_interval));
-
-    index_manga(None)
-        .join(JoinType::LeftJoin, entity::reading::Relation::Manga.def().rev())
-        .column_as(reading::Column::MangaId.count(), "count_reading")
-        .group_by(manga::Column::Id)
-        .filter(manga::Column::UpdatedAt.lte(date_time))
-        .limit(limit)
-        .order_by_desc(reading::Column::MangaId.count())
-        .order_by_asc(manga::Column::UpdatedAt)
-        .having(Expr::cust("COUNT(reading.manga_id) > 0"))
-        .into_model::<data::manga::Full>()
+        .unwrap_or(30000);
+
+    loop {
+        match update_job::claim(db, worker).await {
+            Ok(Some(job)) => run(db, &job).await,
+            Ok(None) => time::sleep(Duration::from_millis(poll_interval_ms)).await,
+            Err(e) => {
+                error!("[Auto Update] Worker {} failed to claim a job: {}", worker, e.message());
+                time::sleep(Duration::from_millis(poll_interval_ms)).await;
+            }
+        }
+    }
+}
+
+/// Update the manga of a job and notify its readers of new chapters
+async fn run(db: &DatabaseConnection, job: &entity::update_job::Model) {
+    let manga = match get_manga_by_id(db, None, job.manga_id).await {
+        Ok(manga) => manga,
+        Err(e) => {
+            error!("[Auto Update] Failed to find manga {}: {}", job.manga_id, e.message());
+            return finish(db, job, Err(e.message())).await;
+        }
+    };
+
+    info!("[Auto Update] Automatically updating {}", manga.url);
+    let url = match Url::parse(&manga.url) {
+        Ok(url) => url,
+        Err(e) => {
+            error!("[Auto Update] URL Failed to Parse: {:#?}", e);
+            let error = proto::ScrapeError {
+                r#type: ScrapeErrorType::NotAValidUrl.into(),
+                message: e.to_string(),
+            };
+            if let Err(e) = scrape_failure::record(db, manga.id, &error).await {
+                error!("[Auto Update] Failed to save scrape failure: {}", e.message());
+            }
+            return finish(db, job, Err(&error.message)).await;
+        }
+    };
+
+    // Chapters are inserted with increasing IDs, so everything after this one is new
+    let last_chapter_id = last_chapter_id(db, manga.id).await;
+
+    match save_manga(db, None, Some(manga.id), url).await {
+        Ok(saved) => {
+            info!("[Auto Update] Successfully updated {}", saved.url);
+            match last_chapter_id {
+                Ok(last_chapter_id) => match new_chapter_ids(db, manga.id, last_chapter_id).await {
+                    Ok(chapter_ids) if chapter_ids.is_empty() => {}
+                    Ok(chapter_ids) => notify_readers(db, &saved, chapter_ids),
+                    Err(e) => error!("[Auto Update] Failed to find new chapters of {}: {}", manga.id, e),
+                },
+                Err(e) => error!("[Auto Update] Failed to find last chapter of {}: {}", manga.id, e),
+            }
+            finish(db, job, Ok(())).await;
+        }
+        Err(e) => {
+            // Scrape failures are saved by `save_manga`
+            error!("[Auto Update] Failed to update {}: {}", manga.url, e.message());
+            finish(db, job, Err(e.message())).await;
+        }
+    }
+}
+
+/// Schedule the next update, or retry the job later if it failed
+async fn finish(db: &DatabaseConnection, job: &entity::update_job::Model, result: Result<(), &str>) {
+    let res = match result {
+        Ok(()) => update_job::complete(db, job).await,
+        Err(error) => update_job::retry(db, job, error).await,
+    };
+
+    if let Err(e) = res {
+        error!(
+            "[Auto Update] Failed to finish job of manga {}: {}",
+            job.manga_id,
+            e.message()
+        );
+    }
+}
+
+/// Highest chapter ID of a manga, including deleted chapters
+async fn last_chapter_id(db: &DatabaseConnection, manga_id: i32) -> Result<Option<i32>, DbErr> {
+    let last = entity::chapter::Entity::find()
+        .select_only()
+        .column_as(entity::chapter::Column::Id.max(), "id")
+        .filter(entity::chapter::Column::MangaId.eq(manga_id))
+        .into_tuple::<Option<i32>>()
+        .one(db)
+        .await?;
+
+    Ok(last.flatten())
+}
+
+/// Chapters that were inserted after the given chapter, in reading order
+async fn new_chapter_ids(db: &DatabaseConnection, manga_id: i32, after: Option<i32>) -> Result<Vec<i32>, DbErr> {
+    entity::chapter::Entity::find()
+        .select_only()
+        .column(entity::chapter::Column::Id)
+        .filter(entity::chapter::Column::MangaId.eq(manga_id))
+        .filter(entity::chapter::Column::DeletedAt.is_null())
+        .apply_if(after, |query, after| {
+            query.filter(entity::chapter::Column::Id.gt(after))
+        })
+        .order_by_asc(entity::chapter::Column::Position)
+        .into_tuple::<i32>()
         .all(db)
         .await
-        .unwrap()
 }
 
-async fn get_readers(db: &DatabaseConnection, manga_id: i32) -> Vec<entity::user::Model> {
-    entity::manga::Entity::find_by_id(manga_id)
-        .find_with_related(entity::user::Entity)
+/// Whether a reader wants to hear about new chapters, when they were at the given amount of chapters
+fn wants_notification(reading: &entity::reading::Model, previous_count: i64) -> bool {
+    reading.status != ReadingStatus::Dropped
+        && !reading.muted
+        && (!reading.notify_caught_up || i64::from(reading.progress) >= previous_count)
+}
+
+/// Readers of a manga that want to hear about its new chapters
+async fn get_readers(
+    db: &DatabaseConnection,
+    manga_id: i32,
+    previous_count: i64,
+) -> Result<Vec<entity::user::Model>, DbErr> {
+    Ok(entity::reading::Entity::find()
+        .filter(entity::reading::Column::MangaId.eq(manga_id))
+        .find_also_related(entity::user::Entity)
         .all(db)
-        .await
-        .unwrap()
+        .await?
         .into_iter()
-        .flat_map(|(_, users)| users)
-        .collect()
+        .filter(|(reading, _)| wants_notification(reading, previous_count))
+        .filter_map(|(_, user)| user)
+        .collect())
 }
 
-async fn send_notification(manga: &data::manga::Full, ids: &[String]) {
-    let client = FcmClient::builder()
-        .service_account_key_json_path("manga-reader-5c535-148af5dd8096.json")
-        .build()
-        .await;
-
-    if let Ok(client) = client {
-        info!("Sending notifications to {} users", ids.len());
-
-        let data = json!({ "manga_id": manga.id });
-
-        for target in ids.to_owned() {
-            let message = Message {
-                data: Some(data.clone()),
-                notification: Some(Notification {
-                    title: Some("Manga Updated!".to_string()),
-                    body: Some(manga.title.to_string()),
-                    image: None,
-                }),
-                target: Target::Token(target),
-                android: None,
-                webpush: None,
-                apns: None,
-                fcm_options: None,
-            };
-
-            let response = client.send(message).await.unwrap();
-            info!("Sent: {:?}", response);
+/// Notify the readers of a manga of new chapters in the background
+fn notify_readers(db: &DatabaseConnection, manga: &MangaReply, chapter_ids: Vec<i32>) {
+    let db = db.clone();
+    let previous_count = manga.count_chapters - chapter_ids.len() as i64;
+    let new_chapters = NewChapters {
+        manga_id: manga.id,
+        manga_title: manga.title.clone(),
+        chapter_ids,
+    };
+
+    tokio::spawn(async move {
+        match get_readers(&db, new_chapters.manga_id, previous_count).await {
+            Ok(readers) if readers.is_empty() => {}
+            Ok(readers) => notifier::notify_users(&db, readers, &new_chapters).await,
+            Err(e) => error!(
+                "[Auto Update] Failed to get readers of {}: {}",
+                new_chapters.manga_id, e
+            ),
         }
-    } else {
-        info!("FCM Error: {:#?}", client.err().unwrap());
-    }
+    });
+}
+
+#[test]
+fn notify_only_interested_readers() {
+    let now = Utc::now().naive_utc();
+    let reading = entity::reading::Model {
+        user_id: 1,
+        manga_id: 1,
+        progress: 8,
+        created_at: now,
+        updated_at: now,
+        status: ReadingStatus::Reading,
+        muted: false,
+        notify_caught_up: false,
+    };
+
+    assert!(wants_notification(&reading, 10));
+    assert!(!wants_notification(
+        &entity::reading::Model {
+            muted: true,
+            ..reading.clone()
+        },
+        10
+    ));
+    assert!(!wants_notification(
+        &entity::reading::Model {
+            status: ReadingStatus::Dropped,
+            ..reading.clone()
+        },
+        10
+    ));
+
+    let caught_up_only = entity::reading::Model {
+        notify_caught_up: true,
+        ..reading
+    };
+    assert!(!wants_notification(&caught_up_only, 10));
+    assert!(wants_notification(&caught_up_only, 8));
 }
@@ -1,5 +1,8 @@
+use std::net::IpAddr;
+
 use migration::DbErr;
 use regex::Regex;
+use reqwest::Url;
 use tonic::Status;
 
 lazy_static! {
@@ -68,6 +71,76 @@ pub fn password(email: &str) -> Result<String, Status> {
     }
 }
 
+/// Verify that collection name:
+/// - is not empty
+/// - is at most 63 characters
+pub fn collection_name(name: &str) -> Result<String, Status> {
+    let name = name.trim();
+
+    if name.is_empty() {
+        Err(Status::invalid_argument("Collection name should not be empty"))
+    } else if name.chars().count() > 63 {
+        Err(Status::invalid_argument(
+            "Collection name should be at most 63 characters",
+        ))
+    } else {
+        Ok(String::from(name))
+    }
+}
+
+/// Verify that url:
+/// - is a valid absolute URL
+/// - uses http or https
+pub fn http_url(url: &str) -> Result<String, Status> {
+    let url = url.trim();
+
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.has_host() => Ok(String::from(url)),
+        _ => Err(Status::invalid_argument("URL should be a valid http(s) URL")),
+    }
+}
+
+/// Whether an IP address is on the internet, so not loopback, private, link-local or unspecified
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified() || ip.is_broadcast())
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => !(ip.is_loopback() || ip.is_unspecified() || ip.is_unique_local() || ip.is_unicast_link_local()),
+        },
+    }
+}
+
+/// Verify that url:
+/// - is a valid absolute URL
+/// - uses http or https
+/// - only resolves to public IP addresses, so the server can not be made to call services in its own network
+///
+/// A host that can not be resolved right now is unavailable instead of invalid
+pub async fn public_http_url(url: &str) -> Result<String, Status> {
+    let url = http_url(url)?;
+    let parsed = Url::parse(&url).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+    let host = parsed.host_str().unwrap_or_default();
+    // IPv6 addresses are written in brackets in URLs
+    let ips: Vec<IpAddr> = match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(ip) => vec![ip],
+        Err(_) => tokio::net::lookup_host((host, parsed.port_or_known_default().unwrap_or(80)))
+            .await
+            .map_err(|e| Status::unavailable(format!("Could not resolve {host}: {e}")))?
+            .map(|addr| addr.ip())
+            .collect(),
+    };
+
+    if ips.is_empty() || !ips.into_iter().all(is_public_ip) {
+        return Err(Status::invalid_argument("URL should point to a public address"));
+    }
+
+    Ok(url)
+}
+
 /// Verify DB Error is a Conflict Error
 pub fn is_conflict(err: &DbErr) -> bool {
     if let DbErr::Query(sea_orm::RuntimeErr::SqlxError(e)) = err {
@@ -79,3 +152,25 @@ pub fn is_conflict(err: &DbErr) -> bool {
     }
     false
 }
+
+#[test]
+fn public_ips() {
+    for ip in ["93.184.215.14", "2606:2800:21f:cb07:6820:80da:af6b:8b2c"] {
+        assert!(is_public_ip(ip.parse().unwrap()), "{ip}");
+    }
+    for ip in [
+        "127.0.0.1",
+        "10.0.0.1",
+        "172.16.0.1",
+        "192.168.1.1",
+        "169.254.169.254",
+        "0.0.0.0",
+        "::1",
+        "::",
+        "fd00::1",
+        "fe80::1",
+        "::ffff:127.0.0.1",
+    ] {
+        assert!(!is_public_ip(ip.parse().unwrap()), "{ip}");
+    }
+}